@@ -1,5 +1,8 @@
-use clap::{Parser, Subcommand};
+use crate::core::overrides::ConflictFix;
+use crate::core::DatabaseKind;
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(
@@ -14,8 +17,24 @@ pub struct App {
     /// Enable verbose logging
     #[arg(short, long, global = true)]
     pub verbose: bool,
+
+    /// Print the file changes as a unified diff without writing anything (commands
+    /// that cannot preview their work refuse it)
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Print the file changes as a unified diff after applying them
+    #[arg(long, global = true)]
+    pub diff: bool,
+
+    /// Tomcat directory to use when the bundle contains several (name or path);
+    /// defaults to the newest, and commands that write files ask in a terminal
+    #[arg(long, global = true)]
+    pub tomcat: Option<PathBuf>,
 }
 
+// Parsed once per run, so the size of the `Configure` variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum AppCommands {
     /// Initialize a new Liferay bundle from a remote source
@@ -30,30 +49,150 @@ pub enum AppCommands {
         #[arg(long)]
         base_url: Option<String>,
         /// Target directory name for the bundle
-        #[arg(short, long)]
-        name: String,
+        #[arg(short, long, required_unless_present = "from_workspace")]
+        name: Option<String>,
+        /// Initialize the bundle declared in the enclosing Liferay Workspace
+        #[arg(long, conflicts_with_all = ["product", "url", "name"])]
+        from_workspace: bool,
+        /// Workspace environment whose configs are applied (defaults to liferay.workspace.environment or 'local')
+        #[arg(long, requires = "from_workspace")]
+        environment: Option<String>,
     },
     /// Configure a Liferay bundle for a specific instance ID
     Configure {
-        /// Unique ID (e.g., 1, 2) to derive ports (8180, 8280) and sessions
-        instance_id: u16,
+        /// Unique ID (e.g., 1, 2) to derive ports (8180, 8280) and sessions, or `auto`
+        #[arg(required_unless_present = "next_free")]
+        instance_id: Option<InstanceSelector>,
+        /// Pick the lowest instance ID whose ports are all free (same as `auto`)
+        #[arg(long, conflicts_with = "instance_id")]
+        next_free: bool,
         /// Path to the Liferay Workspace
         #[arg(short, long)]
         workspace_path: Option<PathBuf>,
-        /// Optional custom HSQL database name
+        /// Database type to connect the instance to (defaults to the current one, or HSQL)
+        #[arg(long, value_enum)]
+        db: Option<DatabaseKind>,
+        /// Optional custom database or schema name (defaults to lportal_<ID>)
         #[arg(short, long)]
         db_name: Option<String>,
+        /// Database host (external databases only)
+        #[arg(long)]
+        db_host: Option<String>,
+        /// Database port (defaults to 5432 for PostgreSQL, 3306 for MySQL/MariaDB)
+        #[arg(long)]
+        db_port: Option<u16>,
+        /// Database user
+        #[arg(long)]
+        db_user: Option<String>,
+        /// Database password
+        #[arg(long)]
+        db_password: Option<String>,
         /// Wipe persistent data (Elasticsearch indexes and HSQL)
         #[arg(long)]
         clear_data: bool,
+        /// Initial and maximum heap size written to setenv.sh (e.g. 4g)
+        #[arg(long)]
+        heap: Option<String>,
+        /// Extra JVM option written to setenv.sh (repeatable)
+        #[arg(long = "jvm-opt", allow_hyphen_values = true)]
+        jvm_opts: Vec<String>,
+        /// Pin a JDK in setenv.sh by major version (e.g. 17) or home directory
+        #[arg(long)]
+        java: Option<String>,
+        /// Add an HTTPS connector on the instance's SSL port with a self-signed certificate
+        #[arg(long)]
+        https: bool,
+        /// Serve the instance on its own host name (e.g. app2.localhost) so cookies don't collide
+        #[arg(long)]
+        host: Option<String>,
+        /// Skip the setup wizard, pre-seeding the company and admin options below
+        #[arg(long)]
+        no_wizard: bool,
+        /// Default company name (with --no-wizard)
+        #[arg(long, requires = "no_wizard")]
+        company_name: Option<String>,
+        /// Default admin screen name (with --no-wizard)
+        #[arg(long, requires = "no_wizard")]
+        admin_screen_name: Option<String>,
+        /// Default admin email; its domain becomes the company web ID (with --no-wizard)
+        #[arg(long, requires = "no_wizard")]
+        admin_email: Option<String>,
+        /// Default admin password (with --no-wizard)
+        #[arg(long, requires = "no_wizard")]
+        admin_password: Option<String>,
+        /// Default company locale, e.g. en_US (with --no-wizard)
+        #[arg(long, requires = "no_wizard")]
+        locale: Option<String>,
+        /// Default company time zone, e.g. UTC (with --no-wizard)
+        #[arg(long, requires = "no_wizard")]
+        timezone: Option<String>,
+    },
+    /// Manage the JVM options lfr-local writes to tomcat/bin/setenv.sh
+    Jvm {
+        /// The instance ID to manage
+        instance_id: u16,
+        #[command(subcommand)]
+        action: JvmCommands,
+        /// Path to the Liferay Workspace
+        #[arg(short, long, global = true)]
+        workspace_path: Option<PathBuf>,
     },
     /// Display a summary of the current Liferay configuration
     Summary,
+    /// Diagnose common environment problems that keep an instance from starting
+    Doctor {
+        /// Optional: the instance ID to check (defaults to the current project)
+        instance_id: Option<u16>,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
+    /// Find portal properties that a file Liferay loads later silently overrides
+    Conflicts {
+        /// Settle every conflict without asking: move the winning value into
+        /// portal-ext.properties, or remove the later definitions
+        #[arg(long, value_enum)]
+        fix: Option<ConflictFix>,
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
     /// Check which Liferay instances are currently running
     Status {
         /// Optional: Check a specific instance ID
         instance_id: Option<u16>,
     },
+    /// Start an instance's Tomcat in the background
+    Start {
+        /// The instance ID to start
+        instance_id: u16,
+        /// Enable the JDWP agent on the instance's debug port
+        #[arg(long)]
+        debug: bool,
+        /// Wait for a debugger to attach before starting
+        #[arg(long, requires = "debug")]
+        suspend: bool,
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
+    /// Write IDE run configurations that start an instance and attach a debugger
+    Ide {
+        /// The instance ID to generate configurations for
+        instance_id: u16,
+        /// Write .idea/runConfigurations entries
+        #[arg(long, required_unless_present = "vscode")]
+        intellij: bool,
+        /// Merge entries into .vscode/launch.json and .vscode/tasks.json
+        #[arg(long)]
+        vscode: bool,
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
     /// Kill a running Liferay instance by its ID
     Kill {
         /// The instance ID to terminate
@@ -67,11 +206,194 @@ pub enum AppCommands {
         /// Wipe all persistent data (Databases & Indexes)
         #[arg(long)]
         all: bool,
-        /// Reset portal-ext.properties (Session Cookie and DB URL)
+        /// Reset portal-ext.properties (Session Cookie and JDBC connection)
         #[arg(long)]
         props: bool,
         /// Reset server.xml ports to defaults (8080, 8005, etc.)
         #[arg(long)]
         ports: bool,
+        /// Remove the HTTPS connector and keystore added by `configure --https`
+        #[arg(long)]
+        https: bool,
+    },
+    /// Manage the external database of a configured instance
+    Db {
+        /// Operation to perform
+        #[arg(value_enum)]
+        action: DbAction,
+        /// The instance ID whose database is managed
+        instance_id: u16,
+        /// SQL dump file (written by dump, read by restore)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
+    /// Save and restore instance state (data, OSGi configs, portal-ext.properties)
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCommands,
+        /// Path to the Liferay Workspace
+        #[arg(short, long, global = true)]
+        workspace_path: Option<PathBuf>,
     },
+    /// Pack a configured instance into a portable archive
+    Export {
+        /// The instance ID to export
+        instance_id: u16,
+        /// Archive to write
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Include the data directory (HSQL, document library, search indexes)
+        #[arg(long)]
+        include_data: bool,
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
+    /// Unpack an exported instance and configure it under a new ID
+    Import {
+        /// Archive created by 'export'
+        archive: PathBuf,
+        /// Instance ID to configure the imported bundle as
+        #[arg(long)]
+        id: u16,
+        /// Target directory name (defaults to the archive name)
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// Revert the file changes of the last recorded operation (see history)
+    Undo {
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
+    /// List recorded operations and the files they changed
+    History {
+        /// Path to the Liferay Workspace
+        #[arg(short, long)]
+        workspace_path: Option<PathBuf>,
+    },
+}
+
+impl AppCommands {
+    /// Name of the command if it acts outside the files a `ChangeSet` can preview
+    /// (processes, databases, archives), so `--dry-run` must be refused
+    pub fn without_dry_run(&self) -> Option<&'static str> {
+        match self {
+            AppCommands::Init { .. } => Some("init"),
+            AppCommands::Start { .. } => Some("start"),
+            AppCommands::Kill { .. } => Some("kill"),
+            AppCommands::Db { .. } => Some("db"),
+            AppCommands::Snapshot {
+                action: SnapshotCommands::Save { .. },
+                ..
+            } => Some("snapshot save"),
+            AppCommands::Snapshot {
+                action: SnapshotCommands::Delete { .. },
+                ..
+            } => Some("snapshot delete"),
+            AppCommands::Export { .. } => Some("export"),
+            AppCommands::Import { .. } => Some("import"),
+            _ => None,
+        }
+    }
+
+    /// Whether the command writes to the bundle, where using the wrong Tomcat would matter
+    pub fn writes_files(&self) -> bool {
+        match self {
+            AppCommands::Configure { .. }
+            | AppCommands::Conflicts { .. }
+            | AppCommands::Ide { .. }
+            | AppCommands::Reset { .. }
+            | AppCommands::Undo { .. } => true,
+            AppCommands::Jvm { action, .. } => !matches!(action, JvmCommands::Get),
+            AppCommands::Snapshot { action, .. } => {
+                matches!(action, SnapshotCommands::Restore { .. })
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// Capture the current state of a stopped instance
+    Save {
+        /// The instance ID to snapshot
+        instance_id: u16,
+        /// Name of the snapshot
+        name: String,
+    },
+    /// Replace the state of a stopped instance with a snapshot
+    Restore {
+        /// The instance ID to restore
+        instance_id: u16,
+        /// Name of the snapshot
+        name: String,
+    },
+    /// List the snapshots of an instance
+    List {
+        /// The instance ID whose snapshots are listed
+        instance_id: u16,
+    },
+    /// Delete a snapshot
+    Delete {
+        /// The instance ID owning the snapshot
+        instance_id: u16,
+        /// Name of the snapshot
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum JvmCommands {
+    /// Add or replace JVM options (a new -Xmx replaces the old one)
+    Set {
+        /// Option such as -Dfile.encoding=UTF-8 or -XX:+UseG1GC (repeatable)
+        #[arg(long = "opt", allow_hyphen_values = true)]
+        options: Vec<String>,
+        /// Initial and maximum heap size (e.g. 4g)
+        #[arg(long)]
+        heap: Option<String>,
+    },
+    /// Show the managed and the effective JVM options
+    Get,
+    /// Remove the managed JVM options, keeping the debug port and pinned JAVA_HOME
+    Reset,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DbAction {
+    /// Create the database or schema
+    Create,
+    /// Drop the database or schema
+    Drop,
+    /// Drop and create the database or schema
+    Recreate,
+    /// Write a plain SQL dump
+    Dump,
+    /// Load a plain SQL dump
+    Restore,
+}
+
+/// An explicit instance ID, or `auto` to pick the next free one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InstanceSelector {
+    Auto,
+    Id(u16),
+}
+
+impl FromStr for InstanceSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(InstanceSelector::Auto);
+        }
+        s.parse()
+            .map(InstanceSelector::Id)
+            .map_err(|_| format!("'{}' is neither an instance ID nor 'auto'", s))
+    }
 }
@@ -0,0 +1,260 @@
+use crate::core::config::read_json;
+use crate::core::SnapshotStore;
+use crate::utils::{ChangeSet, CommandRunner};
+use anyhow::Result;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const RECORD_FILE: &str = "operation.json";
+
+/// Instance data can run to gigabytes, so directory backups leave it out; operations
+/// that replace it take a snapshot instead
+const UNCOPIED_DIR: &str = "data";
+
+/// A file or directory touched by an operation. `backup` is `None` if it did not exist before.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackedUpFile {
+    pub path: PathBuf,
+    pub backup: Option<String>,
+}
+
+/// One mutating command and the original contents of the paths it changed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    pub operation: String,
+    pub timestamp: String,
+    pub files: Vec<BackedUpFile>,
+    /// Snapshot taken just before the operation; undo restores the instance state from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+/// Timestamped copies of configuration files kept inside Liferay Home
+pub struct BackupStore {
+    home: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(home: &Path) -> Self {
+        BackupStore {
+            home: home.to_path_buf(),
+        }
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.home.join(".lfr-local/backups")
+    }
+
+    /// Saves the original contents of `files`, and copies of the `replaced` files or
+    /// directories (apart from `data`), before `operation` overwrites them. `snapshot`
+    /// names a snapshot of the state before the operation. Only the last `keep` operations are kept.
+    pub fn record(
+        &self,
+        operation: &str,
+        files: &[(&Path, Option<&str>)],
+        replaced: &[&Path],
+        snapshot: Option<&str>,
+        keep: usize,
+    ) -> Result<()> {
+        if files.is_empty() && replaced.is_empty() && snapshot.is_none() {
+            return Ok(());
+        }
+
+        let now = Local::now();
+        let id = now.format("%Y%m%d-%H%M%S%.3f").to_string();
+        let op_dir = self.dir().join(&id);
+        fs::create_dir_all(&op_dir)?;
+
+        let mut record = Operation {
+            id,
+            operation: operation.to_string(),
+            timestamp: now.to_rfc3339(),
+            files: Vec::new(),
+            snapshot: snapshot.map(str::to_string),
+        };
+        for (index, (path, original)) in files.iter().enumerate() {
+            let backup = match original {
+                Some(content) => {
+                    let name = format!("{}.bak", index);
+                    fs::write(op_dir.join(&name), content)?;
+                    Some(name)
+                }
+                None => None,
+            };
+            record.files.push(BackedUpFile {
+                path: self.relative(path),
+                backup,
+            });
+        }
+        for (index, path) in replaced.iter().enumerate() {
+            if self.relative(path) == Path::new(UNCOPIED_DIR) {
+                continue;
+            }
+            let backup = if path.exists() {
+                let name = format!("{}.bak", files.len() + index);
+                copy_path(path, &op_dir.join(&name))?;
+                Some(name)
+            } else {
+                None
+            };
+            record.files.push(BackedUpFile {
+                path: self.relative(path),
+                backup,
+            });
+        }
+        fs::write(
+            op_dir.join(RECORD_FILE),
+            serde_json::to_string_pretty(&record)?,
+        )?;
+
+        self.prune(keep)
+    }
+
+    /// Relative paths keep backups valid if the bundle is moved
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.home).unwrap_or(path).to_path_buf()
+    }
+
+    /// Lists recorded operations, oldest first
+    pub fn history(&self) -> Vec<Operation> {
+        let mut operations: Vec<Operation> = fs::read_dir(self.dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| read_json(&entry.path().join(RECORD_FILE)).ok())
+            .collect();
+        operations.sort_by(|a, b| a.id.cmp(&b.id));
+        operations
+    }
+
+    /// Restores the files and directories changed by the most recent operation and forgets it
+    pub fn undo(&self) -> Result<Option<Operation>> {
+        let Some(last) = self.history().pop() else {
+            return Ok(None);
+        };
+        let op_dir = self.dir().join(&last.id);
+
+        if let Some(name) = &last.snapshot {
+            let snapshots = SnapshotStore::new(&self.home);
+            let mut changes = ChangeSet::new();
+            snapshots.restore(name, &mut changes)?;
+            let restored = changes.apply();
+            snapshots.clear_staging()?;
+            restored?;
+            snapshots.delete(name)?;
+        }
+        for file in &last.files {
+            let target = self.home.join(&file.path);
+            let backup = file.backup.as_ref().map(|name| op_dir.join(name));
+            match backup {
+                Some(backup) if backup.is_file() => {
+                    fs::copy(backup, &target)?;
+                }
+                backup => {
+                    CommandRunner::force_delete(&target).map_err(|e| anyhow::anyhow!(e))?;
+                    if let Some(backup) = backup {
+                        copy_path(&backup, &target)?;
+                    }
+                }
+            }
+        }
+        fs::remove_dir_all(op_dir)?;
+        Ok(Some(last))
+    }
+
+    fn prune(&self, keep: usize) -> Result<()> {
+        let history = self.history();
+        for old in history.iter().take(history.len().saturating_sub(keep)) {
+            if let Some(name) = &old.snapshot {
+                // Already deleted by hand is fine
+                let _ = SnapshotStore::new(&self.home).delete(name);
+            }
+            fs::remove_dir_all(self.dir().join(&old.id))?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies a file, or a directory with everything in it
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        let options = fs_extra::dir::CopyOptions {
+            content_only: true,
+            ..Default::default()
+        };
+        fs_extra::dir::copy(from, to, &options).map_err(|e| anyhow::anyhow!(e))?;
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_record_and_undo() {
+        let dir = tempdir().unwrap();
+        let home = dir.path();
+        let props = home.join("portal-ext.properties");
+        let created = home.join("setenv.sh");
+        fs::write(&props, "a=1\n").unwrap();
+
+        let data = home.join("data/hypersonic");
+        let configs = home.join("osgi/configs");
+        fs::create_dir_all(&data).unwrap();
+        fs::create_dir_all(&configs).unwrap();
+        fs::write(data.join("lportal.script"), "v1").unwrap();
+        fs::write(configs.join("a.config"), "x=1\n").unwrap();
+        SnapshotStore::new(home).save("pre-restore").unwrap();
+
+        let store = BackupStore::new(home);
+        store
+            .record(
+                "snapshot 1 restore clean",
+                &[(&props, Some("a=1\n")), (&created, None)],
+                &[&home.join("data"), &configs],
+                Some("pre-restore"),
+                1,
+            )
+            .unwrap();
+        // Data is left to the snapshot rather than copied
+        let op_dir = store.dir().join(&store.history()[0].id);
+        assert!(!op_dir.join("2.bak").exists());
+        assert!(op_dir.join("3.bak/a.config").exists());
+
+        fs::write(&props, "a=2\n").unwrap();
+        fs::write(&created, "export X=1\n").unwrap();
+        fs::remove_dir_all(home.join("data")).unwrap();
+        fs::remove_dir_all(&configs).unwrap();
+
+        assert_eq!(store.history().len(), 1);
+        let undone = store.undo().unwrap().unwrap();
+        assert_eq!(undone.operation, "snapshot 1 restore clean");
+        assert_eq!(fs::read_to_string(&props).unwrap(), "a=1\n");
+        assert!(!created.exists());
+        assert_eq!(
+            fs::read_to_string(data.join("lportal.script")).unwrap(),
+            "v1"
+        );
+        assert!(configs.join("a.config").exists());
+        assert!(SnapshotStore::new(home).list().is_empty());
+        assert!(store.history().is_empty());
+
+        // Only the last `keep` operations survive
+        for operation in ["first", "second"] {
+            store
+                .record(operation, &[(&props, None)], &[], None, 1)
+                .unwrap();
+        }
+        let history = store.history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].operation, "second");
+    }
+}
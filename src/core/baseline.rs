@@ -0,0 +1,84 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Pristine copies of the files `configure` rewrites, kept in `.lfr-local/baseline`
+/// inside Liferay Home so `reset` can put back what the bundle originally shipped with.
+pub struct Baseline {
+    home: PathBuf,
+}
+
+impl Baseline {
+    pub fn new(home: &Path) -> Self {
+        Baseline {
+            home: home.to_path_buf(),
+        }
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.home.join(".lfr-local/baseline")
+    }
+
+    pub fn exists(&self) -> bool {
+        self.dir().join("server.xml").exists()
+    }
+
+    /// Copies server.xml, context.xml and portal-ext.properties unless a baseline
+    /// already exists. Returns whether a baseline was recorded.
+    pub fn capture(&self, tomcat: &Path) -> Result<bool> {
+        if self.exists() {
+            return Ok(false);
+        }
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+
+        fs::copy(tomcat.join("conf/context.xml"), dir.join("context.xml")).ok();
+        let props = self.home.join("portal-ext.properties");
+        if props.exists() {
+            fs::copy(props, dir.join("portal-ext.properties"))?;
+        }
+        // Written last: its presence marks the baseline as complete
+        fs::copy(tomcat.join("conf/server.xml"), dir.join("server.xml"))?;
+        Ok(true)
+    }
+
+    pub fn server_xml(&self) -> Option<String> {
+        fs::read_to_string(self.dir().join("server.xml")).ok()
+    }
+
+    pub fn context_xml(&self) -> Option<String> {
+        fs::read_to_string(self.dir().join("context.xml")).ok()
+    }
+
+    /// Original portal-ext.properties; empty if the bundle shipped without one
+    pub fn portal_ext(&self) -> Option<String> {
+        if !self.exists() {
+            return None;
+        }
+        Some(fs::read_to_string(self.dir().join("portal-ext.properties")).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_capture_only_once() {
+        let dir = tempdir().unwrap();
+        let home = dir.path();
+        let tomcat = home.join("tomcat-9.0.90");
+        fs::create_dir_all(tomcat.join("conf")).unwrap();
+        fs::write(tomcat.join("conf/server.xml"), "<Server port=\"8005\"/>").unwrap();
+
+        let baseline = Baseline::new(home);
+        assert!(baseline.capture(&tomcat).unwrap());
+        fs::write(tomcat.join("conf/server.xml"), "<Server port=\"8105\"/>").unwrap();
+        assert!(!baseline.capture(&tomcat).unwrap());
+
+        assert_eq!(baseline.server_xml().unwrap(), "<Server port=\"8005\"/>");
+        assert_eq!(baseline.portal_ext().unwrap(), "");
+        assert!(baseline.context_xml().is_none());
+    }
+}
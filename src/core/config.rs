@@ -1,18 +1,17 @@
 #![allow(dead_code)]
-use regex::Regex;
+use crate::core::{PortPlan, PropertiesDocument};
 use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Reads a specific key from a Liferay .properties file
 pub fn get_property(path: &Path, key: &str) -> Option<String> {
-    let content = fs::read_to_string(path).ok()?;
-    let re = Regex::new(&format!(r"^{}\s*=\s*(.*)", regex::escape(key))).ok()?;
-
-    content
-        .lines()
-        .find_map(|line| re.captures(line))
-        .map(|cap| cap[1].trim().to_string())
+    if !path.exists() {
+        return None;
+    }
+    let doc = PropertiesDocument::load(path).ok()?;
+    doc.get(key).map(|v| v.trim().to_string())
 }
 
 /// Generic JSON file reader
@@ -20,3 +19,45 @@ pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
     let content = fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
     serde_json::from_str(&content).map_err(|e| format!("Parse error: {}", e))
 }
+
+/// User settings read from `~/.liferay/lfr-local/config.json`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct LocalConfig {
+    pub ports: PortPlan,
+    /// Number of operations kept in `.lfr-local/backups` for `undo`
+    pub max_backups: usize,
+}
+
+impl Default for LocalConfig {
+    fn default() -> Self {
+        LocalConfig {
+            ports: PortPlan::default(),
+            max_backups: 20,
+        }
+    }
+}
+
+impl LocalConfig {
+    pub fn path() -> Option<PathBuf> {
+        let home = std::env::var("HOME").ok()?;
+        Some(Path::new(&home).join(".liferay/lfr-local/config.json"))
+    }
+
+    /// Loads and validates the config; a missing file yields the defaults
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = Self::path().filter(|p| p.exists()) else {
+            return Ok(Self::default());
+        };
+        let config: LocalConfig = read_json(&path)
+            .map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))?;
+        config.ports.validate()?;
+        if config.max_backups == 0 {
+            anyhow::bail!(
+                "Invalid config {}: max_backups must be at least 1",
+                path.display()
+            );
+        }
+        Ok(config)
+    }
+}
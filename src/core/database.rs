@@ -0,0 +1,482 @@
+use crate::core::PropertiesDocument;
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// portal-ext.properties keys that make up a JDBC connection
+pub const JDBC_KEYS: &[&str] = &[
+    "jdbc.default.driverClassName",
+    "jdbc.default.url",
+    "jdbc.default.username",
+    "jdbc.default.password",
+];
+
+/// Directories, relative to Tomcat, where Liferay picks up JDBC drivers
+const DRIVER_DIRS: &[&str] = &[
+    "lib",
+    "lib/ext",
+    "webapps/ROOT/WEB-INF/shielded-container-lib",
+    "webapps/ROOT/WEB-INF/lib",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DatabaseKind {
+    Hsql,
+    Postgres,
+    Mysql,
+    Mariadb,
+}
+
+impl DatabaseKind {
+    pub fn driver_class(&self) -> &'static str {
+        match self {
+            DatabaseKind::Hsql => "org.hsqldb.jdbc.JDBCDriver",
+            DatabaseKind::Postgres => "org.postgresql.Driver",
+            DatabaseKind::Mysql => "com.mysql.cj.jdbc.Driver",
+            DatabaseKind::Mariadb => "org.mariadb.jdbc.Driver",
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            DatabaseKind::Hsql => 0,
+            DatabaseKind::Postgres => 5432,
+            DatabaseKind::Mysql | DatabaseKind::Mariadb => 3306,
+        }
+    }
+
+    pub fn default_user(&self) -> &'static str {
+        match self {
+            DatabaseKind::Hsql => "sa",
+            DatabaseKind::Postgres => "postgres",
+            DatabaseKind::Mysql | DatabaseKind::Mariadb => "root",
+        }
+    }
+
+    /// File name prefixes of the driver jars for this database
+    fn driver_jar_prefixes(&self) -> &'static [&'static str] {
+        match self {
+            DatabaseKind::Hsql => &["hsql"],
+            DatabaseKind::Postgres => &["postgresql"],
+            DatabaseKind::Mysql => &["mysql-connector-j", "mysql-connector-java"],
+            DatabaseKind::Mariadb => &["mariadb-java-client"],
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DatabaseKind::Hsql => "HSQL",
+            DatabaseKind::Postgres => "PostgreSQL",
+            DatabaseKind::Mysql => "MySQL",
+            DatabaseKind::Mariadb => "MariaDB",
+        }
+    }
+}
+
+/// A JDBC connection as written to portal-ext.properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseConfig {
+    pub kind: DatabaseKind,
+    pub host: String,
+    pub port: u16,
+    pub name: String,
+    pub user: String,
+    pub password: String,
+}
+
+impl DatabaseConfig {
+    /// Builds a connection using the per-kind defaults for anything not given
+    pub fn new(
+        kind: DatabaseKind,
+        name: String,
+        host: Option<String>,
+        port: Option<u16>,
+        user: Option<String>,
+        password: Option<String>,
+    ) -> Self {
+        DatabaseConfig {
+            kind,
+            host: host.unwrap_or_else(|| "localhost".to_string()),
+            port: port.unwrap_or(kind.default_port()),
+            name,
+            user: user.unwrap_or_else(|| kind.default_user().to_string()),
+            password: password.unwrap_or_default(),
+        }
+    }
+
+    /// The connection `configure` writes: the existing block unless another `kind` is
+    /// requested, otherwise a fresh one. A default `lportal_<ID>` name follows the
+    /// instance ID when it changes.
+    pub fn for_instance(
+        existing: Option<Self>,
+        kind: Option<DatabaseKind>,
+        previous_id: Option<u16>,
+        instance_id: u16,
+    ) -> Self {
+        let default_name = |id: u16| format!("lportal_{}", id);
+        match existing.filter(|e| kind.is_none_or(|k| k == e.kind)) {
+            Some(mut config) => {
+                let derived = match previous_id {
+                    Some(id) => config.name == default_name(id),
+                    // Unconfigured bundles still carry the stock HSQL database
+                    None => config.kind == DatabaseKind::Hsql,
+                };
+                if derived && previous_id != Some(instance_id) {
+                    config.name = default_name(instance_id);
+                }
+                config
+            }
+            None => Self::new(
+                kind.unwrap_or(DatabaseKind::Hsql),
+                default_name(instance_id),
+                None,
+                None,
+                None,
+                None,
+            ),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        match self.kind {
+            DatabaseKind::Hsql => format!(
+                "jdbc:hsqldb:${{liferay.home}}/data/hypersonic/{};hsqldb.write_delay=false",
+                self.name
+            ),
+            DatabaseKind::Postgres => format!(
+                "jdbc:postgresql://{}:{}/{}",
+                self.host, self.port, self.name
+            ),
+            DatabaseKind::Mysql => format!(
+                "jdbc:mysql://{}:{}/{}?characterEncoding=UTF-8&dontTrackOpenResources=true&holdResultsOpenOverStatementClose=true&serverTimezone=GMT&useFastDateParsing=false&useUnicode=true",
+                self.host, self.port, self.name
+            ),
+            DatabaseKind::Mariadb => format!(
+                "jdbc:mariadb://{}:{}/{}?useUnicode=true&characterEncoding=UTF-8&useFastDateParsing=false",
+                self.host, self.port, self.name
+            ),
+        }
+    }
+
+    /// Writes the full jdbc.default.* block
+    pub fn apply(&self, props: &mut PropertiesDocument) {
+        props.set("jdbc.default.driverClassName", self.kind.driver_class());
+        props.set("jdbc.default.url", &self.url());
+        props.set("jdbc.default.username", &self.user);
+        props.set("jdbc.default.password", &self.password);
+    }
+
+    /// Reads the connection back from portal-ext.properties
+    pub fn from_properties(props: &PropertiesDocument) -> Option<Self> {
+        let url = props.get("jdbc.default.url")?;
+        let (kind, rest) = if let Some(rest) = url.strip_prefix("jdbc:hsqldb:") {
+            (DatabaseKind::Hsql, rest)
+        } else if let Some(rest) = url.strip_prefix("jdbc:postgresql://") {
+            (DatabaseKind::Postgres, rest)
+        } else if let Some(rest) = url.strip_prefix("jdbc:mysql://") {
+            (DatabaseKind::Mysql, rest)
+        } else if let Some(rest) = url.strip_prefix("jdbc:mariadb://") {
+            (DatabaseKind::Mariadb, rest)
+        } else {
+            return None;
+        };
+
+        let (host, port, name) = if kind == DatabaseKind::Hsql {
+            let path = rest.split(';').next().unwrap_or_default();
+            let name = path.rsplit('/').next().unwrap_or(path);
+            (String::new(), 0, name.to_string())
+        } else {
+            let rest = rest.split('?').next().unwrap_or_default();
+            let (authority, name) = rest.split_once('/').unwrap_or((rest, ""));
+            let (host, port) = match authority.rsplit_once(':') {
+                Some((h, p)) => (h, p.parse().unwrap_or(kind.default_port())),
+                None => (authority, kind.default_port()),
+            };
+            (host.to_string(), port, name.to_string())
+        };
+
+        Some(DatabaseConfig {
+            kind,
+            host,
+            port,
+            name,
+            user: props
+                .get("jdbc.default.username")
+                .unwrap_or(kind.default_user())
+                .to_string(),
+            password: props
+                .get("jdbc.default.password")
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+
+    /// The password as shown in `summary`
+    pub fn masked_password(&self) -> String {
+        if self.password.is_empty() {
+            "(empty)".to_string()
+        } else {
+            "*".repeat(8)
+        }
+    }
+
+    /// Creates the database (a no-op for HSQL, which creates itself on startup)
+    pub fn create_database(&self) -> anyhow::Result<()> {
+        let sql = match self.kind {
+            DatabaseKind::Hsql => return Ok(()),
+            DatabaseKind::Postgres => {
+                format!("CREATE DATABASE {} ENCODING 'UTF8'", self.identifier()?)
+            }
+            DatabaseKind::Mysql | DatabaseKind::Mariadb => format!(
+                "CREATE DATABASE {} CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci",
+                self.identifier()?
+            ),
+        };
+        run(self.admin_command(&sql))
+    }
+
+    /// Drops the database, or deletes the HSQL files under `liferay_home`
+    pub fn drop_database(&self, liferay_home: &Path) -> anyhow::Result<()> {
+        if self.kind == DatabaseKind::Hsql {
+            let dir = liferay_home.join("data/hypersonic");
+            for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name == self.name || name.starts_with(&format!("{}.", self.name)) {
+                    crate::utils::CommandRunner::force_delete(&entry.path())
+                        .map_err(|e| anyhow::anyhow!(e))?;
+                }
+            }
+            return Ok(());
+        }
+        run(self.admin_command(&format!("DROP DATABASE IF EXISTS {}", self.identifier()?)))
+    }
+
+    /// Writes a plain SQL dump of the database to `file`
+    pub fn dump(&self, file: &Path) -> anyhow::Result<()> {
+        run(self.dump_command(file)?)
+    }
+
+    /// Loads a plain SQL dump produced by `dump` into the database
+    pub fn restore(&self, file: &Path) -> anyhow::Result<()> {
+        let mut cmd = self.restore_command(file)?;
+        if self.kind != DatabaseKind::Postgres {
+            cmd.stdin(std::fs::File::open(file)?);
+        }
+        run(cmd)
+    }
+
+    /// Client invocation that runs a statement against the server (not the instance database)
+    pub fn admin_command(&self, sql: &str) -> Command {
+        let mut cmd = self.client("psql", "mysql", "mariadb");
+        match self.kind {
+            DatabaseKind::Postgres => {
+                cmd.args(["-d", "postgres", "-v", "ON_ERROR_STOP=1", "-c", sql])
+            }
+            _ => cmd.args(["-e", sql]),
+        };
+        cmd
+    }
+
+    pub fn dump_command(&self, file: &Path) -> anyhow::Result<Command> {
+        let name = self.identifier()?;
+        let mut cmd = self.client("pg_dump", "mysqldump", "mariadb-dump");
+        match self.kind {
+            DatabaseKind::Postgres => cmd.arg("-f").arg(file).arg(name),
+            _ => cmd
+                .args(["--single-transaction", "--routines"])
+                .arg(format!("--result-file={}", file.display()))
+                .arg(name),
+        };
+        Ok(cmd)
+    }
+
+    pub fn restore_command(&self, file: &Path) -> anyhow::Result<Command> {
+        let name = self.identifier()?;
+        let mut cmd = self.client("psql", "mysql", "mariadb");
+        match self.kind {
+            DatabaseKind::Postgres => cmd
+                .args(["-v", "ON_ERROR_STOP=1", "-d", name, "-f"])
+                .arg(file),
+            _ => cmd.arg(name),
+        };
+        Ok(cmd)
+    }
+
+    /// Base client command with host, port and credentials for this connection
+    fn client(&self, postgres: &str, mysql: &str, mariadb: &str) -> Command {
+        let mut cmd = match self.kind {
+            DatabaseKind::Postgres => {
+                let mut cmd = Command::new(postgres);
+                cmd.args([
+                    "-h",
+                    &self.host,
+                    "-p",
+                    &self.port.to_string(),
+                    "-U",
+                    &self.user,
+                ]);
+                cmd.env("PGPASSWORD", &self.password);
+                cmd
+            }
+            DatabaseKind::Mysql | DatabaseKind::Mariadb | DatabaseKind::Hsql => {
+                let program = if self.kind == DatabaseKind::Mariadb {
+                    mariadb
+                } else {
+                    mysql
+                };
+                let mut cmd = Command::new(program);
+                cmd.args([
+                    "-h",
+                    &self.host,
+                    "-P",
+                    &self.port.to_string(),
+                    "-u",
+                    &self.user,
+                ]);
+                cmd.env("MYSQL_PWD", &self.password);
+                cmd
+            }
+        };
+        cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+        cmd
+    }
+
+    /// The database name, rejected unless it is safe to splice into SQL
+    fn identifier(&self) -> anyhow::Result<&str> {
+        if self.kind == DatabaseKind::Hsql {
+            anyhow::bail!("This operation is not supported for HSQL databases");
+        }
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            anyhow::bail!("Unsupported database name '{}'", self.name);
+        }
+        Ok(&self.name)
+    }
+
+    /// Looks for this database's JDBC driver jar in the Tomcat class path folders
+    pub fn find_driver_jar(&self, tomcat: &Path) -> Option<PathBuf> {
+        DRIVER_DIRS
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(tomcat.join(dir)).ok())
+            .flat_map(|entries| entries.flatten())
+            .map(|entry| entry.path())
+            .find(|path| {
+                let name = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_lowercase();
+                name.ends_with(".jar")
+                    && self
+                        .kind
+                        .driver_jar_prefixes()
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix))
+            })
+    }
+}
+
+fn run(mut cmd: Command) -> anyhow::Result<()> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let status = cmd
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", program, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        anyhow::bail!("{} failed ({})", program, status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_through_properties() {
+        let config = DatabaseConfig::new(
+            DatabaseKind::Postgres,
+            "lportal_3".to_string(),
+            Some("db.local".to_string()),
+            Some(5433),
+            Some("liferay".to_string()),
+            Some("secret".to_string()),
+        );
+        let mut props = PropertiesDocument::parse("");
+        config.apply(&mut props);
+
+        assert_eq!(
+            props.get("jdbc.default.url"),
+            Some("jdbc:postgresql://db.local:5433/lportal_3")
+        );
+        assert_eq!(DatabaseConfig::from_properties(&props), Some(config));
+    }
+
+    #[test]
+    fn test_reconfigure_keeps_existing_connection() {
+        let mut props = PropertiesDocument::parse(
+            "jdbc.default.driverClassName=org.postgresql.Driver\n\
+             jdbc.default.url=jdbc:postgresql://db.local:5433/lportal_3\n\
+             jdbc.default.username=liferay\n\
+             jdbc.default.password=secret\n",
+        );
+        let before = props.to_string();
+        let existing = DatabaseConfig::from_properties(&props);
+
+        // Re-running configure without --db leaves the block alone
+        DatabaseConfig::for_instance(existing.clone(), None, Some(3), 3).apply(&mut props);
+        assert_eq!(props.to_string(), before);
+
+        // A new instance ID moves the default name along, keeping host and credentials
+        let moved = DatabaseConfig::for_instance(existing.clone(), None, Some(3), 4);
+        assert_eq!(moved.name, "lportal_4");
+        assert_eq!(moved.password, "secret");
+
+        // Asking for another kind starts over from its defaults
+        let hsql = DatabaseConfig::for_instance(existing, Some(DatabaseKind::Hsql), Some(3), 3);
+        assert_eq!(hsql.kind, DatabaseKind::Hsql);
+        assert_eq!(hsql.name, "lportal_3");
+    }
+
+    #[test]
+    fn test_client_commands_use_configured_credentials() {
+        let config = DatabaseConfig::new(
+            DatabaseKind::Mysql,
+            "lportal_2".to_string(),
+            None,
+            None,
+            Some("liferay".to_string()),
+            Some("secret".to_string()),
+        );
+        let cmd = config.dump_command(Path::new("dump.sql")).unwrap();
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+
+        assert_eq!(cmd.get_program(), "mysqldump");
+        assert_eq!(
+            args,
+            [
+                "-h",
+                "localhost",
+                "-P",
+                "3306",
+                "-u",
+                "liferay",
+                "--single-transaction",
+                "--routines",
+                "--result-file=dump.sql",
+                "lportal_2"
+            ]
+        );
+        assert!(cmd
+            .get_envs()
+            .any(|(k, v)| k == "MYSQL_PWD" && v == Some("secret".as_ref())));
+
+        let mut bad = config.clone();
+        bad.name = "x; DROP TABLE y".to_string();
+        assert!(bad.create_database().is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use crate::core::jdk;
+use crate::core::tomcat::additional_connectors;
+use crate::core::{BundleVersion, PortPlan, SetEnv, Workspace};
+use crate::utils::net::is_port_open;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+use sysinfo::{Disks, System};
+
+/// Open files Elasticsearch needs
+const MIN_OPEN_FILES: u64 = 65535;
+/// `vm.max_map_count` Elasticsearch needs on Linux
+const MIN_MAP_COUNT: u64 = 262144;
+const GB: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        }
+    }
+}
+
+/// Outcome of one diagnostic, with a hint on how to fix anything but a pass
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Runs every check against the project at `root` (configured as `instance_id`, if known)
+pub fn run(
+    ws: &impl Workspace,
+    root: &Path,
+    instance_id: Option<u16>,
+    plan: &PortPlan,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let bundles = ws.get_bundles_dir(root);
+
+    let tomcat = match ws.find_tomcat(root) {
+        Ok(tomcat) => {
+            checks.push(Check::pass("Tomcat", tomcat.to_string_lossy()));
+            Some(tomcat)
+        }
+        Err(e) => {
+            checks.push(Check::fail(
+                "Tomcat",
+                e.to_string(),
+                "Run 'lfr-local init' or pass --tomcat",
+            ));
+            None
+        }
+    };
+
+    if let Some(tomcat) = &tomcat {
+        checks.push(check_java(&bundles, tomcat));
+    }
+
+    let running = match instance_id.map(|id| (id, plan.ports_for(id))) {
+        Some((id, Ok(ports))) => {
+            let running = is_port_open(ports.http);
+            let additional = tomcat.as_deref().map_or(0, additional_connectors);
+            checks.push(check_ports(plan, id, additional, running));
+            running
+        }
+        Some((_, Err(e))) => {
+            checks.push(Check::fail(
+                "Ports",
+                e.to_string(),
+                "Pick a lower ID or change the port plan",
+            ));
+            false
+        }
+        None => {
+            checks.push(Check::warn(
+                "Ports",
+                "Project is not configured as an instance",
+                "Run 'lfr-local configure <ID>' or 'lfr-local configure auto'",
+            ));
+            false
+        }
+    };
+
+    checks.push(check_disk(&bundles));
+    checks.push(check_memory());
+    if cfg!(unix) {
+        checks.push(check_open_files());
+    }
+    if let Some(check) = check_map_count() {
+        checks.push(check);
+    }
+    checks.push(check_osgi_state(&bundles, tomcat.as_deref()));
+    checks.push(check_sidecars(&bundles, running));
+    checks
+}
+
+/// Worst status of all checks
+pub fn overall(checks: &[Check]) -> CheckStatus {
+    checks
+        .iter()
+        .map(|c| c.status)
+        .max()
+        .unwrap_or(CheckStatus::Pass)
+}
+
+fn check_java(bundles: &Path, tomcat: &Path) -> Check {
+    let setenv =
+        SetEnv::parse(&fs::read_to_string(tomcat.join("bin/setenv.sh")).unwrap_or_default());
+    let pinned = setenv.variable("JAVA_HOME").map(PathBuf::from);
+    let Some((jdk, source)) = jdk::active(pinned.as_deref()) else {
+        return Check::fail(
+            "Java",
+            match &pinned {
+                Some(home) => format!("Pinned JDK {} not found", home.display()),
+                None => "No JDK found".to_string(),
+            },
+            "Install a supported JDK and run 'lfr-local configure <ID> --java <VERSION>'",
+        );
+    };
+
+    let version = BundleVersion::detect(bundles, tomcat);
+    let message = format!("{}, {}", jdk.describe(), source);
+    match version.java_warning(jdk.major()) {
+        Some(warning) => {
+            let suggested = version
+                .supported_java()
+                .and_then(|s| s.last())
+                .copied()
+                .unwrap_or_default();
+            Check::fail(
+                "Java",
+                format!("{}: {}", message, warning),
+                format!("Run 'lfr-local configure <ID> --java {}'", suggested),
+            )
+        }
+        None if version.version.is_none() => Check::warn(
+            "Java",
+            format!("{} (Liferay version unknown)", message),
+            "Check the bundle's supported JDKs in the compatibility matrix",
+        ),
+        None => Check::pass("Java", message),
+    }
+}
+
+fn check_ports(plan: &PortPlan, id: u16, additional: u16, running: bool) -> Check {
+    if running {
+        return Check::pass("Ports", format!("Instance {} is running", id));
+    }
+    match plan.conflicts(id, additional, is_port_open) {
+        Ok(conflicts) if conflicts.is_empty() => {
+            Check::pass("Ports", format!("All ports of instance {} are free", id))
+        }
+        Ok(conflicts) => {
+            let list: Vec<String> = conflicts
+                .iter()
+                .map(|(name, port)| format!("{} ({})", port, name))
+                .collect();
+            Check::fail(
+                "Ports",
+                format!("In use or reserved: {}", list.join(", ")),
+                "Stop the program holding the port or move with 'lfr-local configure auto'",
+            )
+        }
+        Err(e) => Check::fail("Ports", e.to_string(), "Change the port plan"),
+    }
+}
+
+fn check_disk(bundles: &Path) -> Check {
+    let path = fs::canonicalize(bundles).unwrap_or_else(|_| bundles.to_path_buf());
+    let disks = Disks::new_with_refreshed_list();
+    let Some(disk) = disks
+        .iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+    else {
+        return Check::warn(
+            "Disk space",
+            "Could not determine the disk of Liferay Home",
+            "Make sure a few GB are free",
+        );
+    };
+
+    let free = disk.available_space();
+    let message = format!(
+        "{:.1} GB free on {}",
+        free as f64 / GB as f64,
+        disk.mount_point().display()
+    );
+    let hint = "Free up space or remove old snapshots and templates";
+    if free < 2 * GB {
+        Check::fail("Disk space", message, hint)
+    } else if free < 10 * GB {
+        Check::warn("Disk space", message, hint)
+    } else {
+        Check::pass("Disk space", message)
+    }
+}
+
+fn check_memory() -> Check {
+    let mut sys = System::new();
+    sys.refresh_memory();
+    let available = sys.available_memory();
+    let message = format!("{:.1} GB available", available as f64 / GB as f64);
+    if available < 2 * GB {
+        Check::warn(
+            "Memory",
+            message,
+            "Close other programs or lower the heap with 'lfr-local jvm <ID> set --heap'",
+        )
+    } else {
+        Check::pass("Memory", message)
+    }
+}
+
+fn check_open_files() -> Check {
+    let limit = Command::new("sh")
+        .args(["-c", "ulimit -n"])
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    match limit.as_deref() {
+        Some("unlimited") => Check::pass("Open files", "unlimited"),
+        Some(value) => match value.parse::<u64>() {
+            Ok(n) if n >= MIN_OPEN_FILES => Check::pass("Open files", value),
+            Ok(_) => Check::warn(
+                "Open files",
+                format!("Limit is {}, Elasticsearch needs {}", value, MIN_OPEN_FILES),
+                format!("Run 'ulimit -n {}' before starting", MIN_OPEN_FILES),
+            ),
+            Err(_) => Check::warn(
+                "Open files",
+                "Could not read the limit",
+                "Check 'ulimit -n'",
+            ),
+        },
+        None => Check::warn(
+            "Open files",
+            "Could not read the limit",
+            "Check 'ulimit -n'",
+        ),
+    }
+}
+
+fn check_map_count() -> Option<Check> {
+    let value: u64 = fs::read_to_string("/proc/sys/vm/max_map_count")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(if value >= MIN_MAP_COUNT {
+        Check::pass("vm.max_map_count", value.to_string())
+    } else {
+        Check::warn(
+            "vm.max_map_count",
+            format!("{}, Elasticsearch needs {}", value, MIN_MAP_COUNT),
+            format!("Run 'sudo sysctl -w vm.max_map_count={}'", MIN_MAP_COUNT),
+        )
+    })
+}
+
+/// Newest modification time of the entries directly inside `dir`
+fn newest_entry(dir: &Path) -> Option<SystemTime> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|e| e.metadata().ok()?.modified().ok())
+        .max()
+}
+
+fn check_osgi_state(bundles: &Path, tomcat: Option<&Path>) -> Check {
+    let state = bundles.join("osgi/state");
+    let Some(state_time) = fs::metadata(&state).and_then(|m| m.modified()).ok() else {
+        return Check::pass("OSGi state", "No cached state");
+    };
+
+    let mut sources: Vec<PathBuf> = ["core", "marketplace", "modules", "portal", "static"]
+        .iter()
+        .map(|dir| bundles.join("osgi").join(dir))
+        .collect();
+    if let Some(tomcat) = tomcat {
+        sources.push(tomcat.join("webapps/ROOT/WEB-INF/lib"));
+        sources.push(tomcat.join("webapps/ROOT/WEB-INF/shielded-container-lib"));
+    }
+    let stale = sources
+        .iter()
+        .filter_map(|dir| newest_entry(dir))
+        .any(|modified| modified > state_time);
+    if stale {
+        Check::warn(
+            "OSGi state",
+            "osgi/state is older than installed modules",
+            "Run 'lfr-local reset' to clear the cached state",
+        )
+    } else {
+        Check::pass("OSGi state", "Up to date")
+    }
+}
+
+/// Elasticsearch processes started from this bundle's sidecar directory
+fn check_sidecars(bundles: &Path, running: bool) -> Check {
+    let home = fs::canonicalize(bundles)
+        .unwrap_or_else(|_| bundles.to_path_buf())
+        .to_string_lossy()
+        .to_string();
+    let mut sys = System::new();
+    sys.refresh_processes();
+    let pids: Vec<String> = sys
+        .processes()
+        .values()
+        .filter(|p| {
+            let cmd = p.cmd();
+            cmd.iter()
+                .any(|arg| arg.contains("org.elasticsearch.bootstrap.Elasticsearch"))
+                && cmd.iter().any(|arg| arg.contains(&home))
+        })
+        .map(|p| p.pid().to_string())
+        .collect();
+
+    if pids.is_empty() {
+        Check::pass("Elasticsearch sidecar", "No sidecar processes")
+    } else if running {
+        Check::pass(
+            "Elasticsearch sidecar",
+            format!("Running (PID {})", pids.join(", ")),
+        )
+    } else {
+        Check::warn(
+            "Elasticsearch sidecar",
+            format!("Leftover processes: PID {}", pids.join(", ")),
+            format!("Stop them with 'kill {}'", pids.join(" ")),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_osgi_state_older_than_modules_is_stale() {
+        let dir = tempdir().unwrap();
+        let bundles = dir.path();
+        fs::create_dir_all(bundles.join("osgi/state")).unwrap();
+        assert_eq!(check_osgi_state(bundles, None).status, CheckStatus::Pass);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::create_dir_all(bundles.join("osgi/modules")).unwrap();
+        fs::write(bundles.join("osgi/modules/custom.jar"), "jar").unwrap();
+        let check = check_osgi_state(bundles, None);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.hint.is_some());
+
+        assert_eq!(overall(&[check]), CheckStatus::Warn);
+    }
+}
@@ -1,4 +1,8 @@
+use crate::core::config::get_property;
+use crate::core::BundleResolver;
+use dialoguer::Select;
 use std::fs;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, PartialEq)]
@@ -20,15 +24,40 @@ pub trait Workspace {
     /// Returns the Liferay version if detectable (e.g. from gradle.properties)
     fn get_liferay_version(&self, root: &Path) -> Option<String>;
 
+    /// Reads a workspace setting, honouring gradle-local.properties overrides
+    fn get_workspace_property(&self, root: &Path, key: &str) -> Option<String>;
+
+    /// Returns the configured Liferay home (liferay.workspace.home.dir, default 'bundles'),
+    /// whether or not it exists yet
+    fn get_workspace_home(&self, root: &Path) -> PathBuf;
+
     /// Specifically for local DXP: Finds the Tomcat directory inside 'bundles'
     fn find_tomcat(&self, root: &Path) -> anyhow::Result<PathBuf>;
 
+    /// Lists every app-server directory in the bundle, newest version first
+    fn find_tomcat_candidates(&self, root: &Path) -> anyhow::Result<Vec<PathBuf>>;
+
     /// Returns the directory that contains Liferay data/osgi/deploy folders
     fn get_bundles_dir(&self, root: &Path) -> PathBuf;
+
+    /// Returns the instance ID a project was configured with (from its session cookie)
+    fn get_instance_id(&self, root: &Path) -> Option<u16>;
+
+    /// Finds the project configured as `instance_id`: the current project, or a
+    /// bundle directly below the working directory
+    fn find_instance(&self, instance_id: u16) -> anyhow::Result<PathBuf>;
+
+    /// IDs taken by bundles directly below the working directory, other than the
+    /// current project (which may keep its own ID)
+    fn known_instance_ids(&self) -> Vec<u16>;
 }
 
 pub struct LiferayProject {
     pub current_dir: PathBuf,
+    /// Tomcat directory chosen with `--tomcat` (a name inside the bundle or a path)
+    pub tomcat: Option<PathBuf>,
+    /// Ask which Tomcat to use when a bundle has several; otherwise the newest is used
+    pub prompt_tomcat: bool,
 }
 
 impl Workspace for LiferayProject {
@@ -36,9 +65,7 @@ impl Workspace for LiferayProject {
         let mut path = self.current_dir.clone();
         loop {
             // Liferay Workspace (Traditional)
-            if path.join("bundles").exists()
-                || path.join("gradle.properties").exists() && path.join("modules").exists()
-            {
+            if is_liferay_workspace(&path) {
                 return Ok(path);
             }
 
@@ -69,7 +96,7 @@ impl Workspace for LiferayProject {
             ProjectType::LiferayCloud
         } else if root.join("client-extension.yaml").exists() {
             ProjectType::ClientExtension
-        } else if root.join("bundles").exists() || root.join("gradle.properties").exists() {
+        } else if is_liferay_workspace(root) {
             ProjectType::LiferayWorkspace
         } else if root.join("osgi").exists() && root.join("deploy").exists() {
             ProjectType::StandaloneBundle
@@ -79,56 +106,185 @@ impl Workspace for LiferayProject {
     }
 
     fn get_liferay_version(&self, root: &Path) -> Option<String> {
-        let paths = vec![
-            root.join("liferay").join("gradle.properties"),
-            root.join("gradle.properties"),
-        ];
-
-        for path in paths {
-            if let Ok(content) = fs::read_to_string(path) {
-                for line in content.lines() {
-                    if line.starts_with("liferay.workspace.product=") {
-                        let product = line.split('=').nth(1)?.trim();
-                        if product.contains("7.4") || product.starts_with("dxp-202") {
-                            return Some("7.4".to_string());
-                        }
-                        if product.contains("7.3") {
-                            return Some("7.3".to_string());
-                        }
-                        if product.contains("7.2") {
-                            return Some("7.2".to_string());
-                        }
-                        if product.contains("7.1") {
-                            return Some("7.1".to_string());
-                        }
-                    }
-                }
-            }
+        let product = get_property(
+            &root.join("liferay").join("gradle.properties"),
+            "liferay.workspace.product",
+        )
+        .or_else(|| self.get_workspace_property(root, "liferay.workspace.product"))?;
+
+        if product.contains("7.4") || product.starts_with("dxp-202") {
+            return Some("7.4".to_string());
+        }
+        if product.contains("7.3") {
+            return Some("7.3".to_string());
+        }
+        if product.contains("7.2") {
+            return Some("7.2".to_string());
+        }
+        if product.contains("7.1") {
+            return Some("7.1".to_string());
         }
         None
     }
 
+    fn get_workspace_property(&self, root: &Path, key: &str) -> Option<String> {
+        ["gradle-local.properties", "gradle.properties"]
+            .iter()
+            .find_map(|file| get_property(&root.join(file), key))
+    }
+
+    fn get_workspace_home(&self, root: &Path) -> PathBuf {
+        let home_dir = self
+            .get_workspace_property(root, "liferay.workspace.home.dir")
+            .unwrap_or_else(|| "bundles".to_string());
+        root.join(home_dir)
+    }
+
     fn find_tomcat(&self, root: &Path) -> anyhow::Result<PathBuf> {
         let bundles = self.get_bundles_dir(root);
-        let entries = fs::read_dir(&bundles)?;
 
-        for entry in entries.flatten() {
-            let name = entry.file_name().to_string_lossy().to_lowercase();
-            if (name.starts_with("tomcat-") || name == "tomcat") && entry.path().is_dir() {
-                return Ok(entry.path());
+        if let Some(choice) = &self.tomcat {
+            let path = if choice.is_absolute() || choice.components().count() > 1 {
+                choice.clone()
+            } else {
+                bundles.join(choice)
+            };
+            if !path.is_dir() {
+                anyhow::bail!("Tomcat directory not found: {}", path.display());
             }
+            return Ok(path);
+        }
+
+        let mut candidates = self.find_tomcat_candidates(root)?;
+        if candidates.len() <= 1 {
+            return candidates.pop().ok_or_else(|| {
+                anyhow::anyhow!("Tomcat directory not found in {}", bundles.display())
+            });
+        }
+
+        // Only commands that write files ask; reading from the newest is harmless
+        if self.prompt_tomcat && std::io::stdin().is_terminal() {
+            let names: Vec<String> = candidates
+                .iter()
+                .map(|c| {
+                    c.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect();
+            let selection = Select::new()
+                .with_prompt("Multiple Tomcat directories found. Which one should be used?")
+                .items(&names)
+                .default(0)
+                .interact_opt()
+                .map_err(|e| anyhow::anyhow!("Tomcat selection failed: {}", e))?
+                .ok_or_else(|| {
+                    anyhow::anyhow!("No Tomcat selected; pass --tomcat to choose one")
+                })?;
+            return Ok(candidates.swap_remove(selection));
         }
-        anyhow::bail!("Tomcat directory not found in {}", bundles.display())
+
+        Ok(candidates.swap_remove(0))
+    }
+
+    fn find_tomcat_candidates(&self, root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let bundles = self.get_bundles_dir(root);
+        let mut candidates: Vec<PathBuf> = fs::read_dir(&bundles)?
+            .flatten()
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                (name.starts_with("tomcat-") || name == "tomcat") && entry.path().is_dir()
+            })
+            .map(|entry| entry.path())
+            .collect();
+
+        candidates.sort_by(|a, b| {
+            let name_a = a.file_name().unwrap_or_default().to_string_lossy();
+            let name_b = b.file_name().unwrap_or_default().to_string_lossy();
+            BundleResolver::compare_versions(&name_b, &name_a)
+        });
+        Ok(candidates)
     }
 
     fn get_bundles_dir(&self, root: &Path) -> PathBuf {
-        let bundles = root.join("bundles");
+        let bundles = self.get_workspace_home(root);
         if bundles.exists() {
             bundles
         } else {
             root.to_path_buf()
         }
     }
+
+    fn get_instance_id(&self, root: &Path) -> Option<u16> {
+        let bundles = self.get_bundles_dir(root);
+        get_property(
+            &bundles.join("portal-ext.properties"),
+            "session.cookie.name",
+        )?
+        .strip_prefix("LFR_SESSION_")?
+        .parse()
+        .ok()
+    }
+
+    fn find_instance(&self, instance_id: u16) -> anyhow::Result<PathBuf> {
+        let current = self.find_root().ok();
+        if let Some(root) = &current {
+            if self.get_instance_id(root) == Some(instance_id) {
+                return Ok(root.clone());
+            }
+        }
+
+        for entry in fs::read_dir(&self.current_dir)?.flatten() {
+            let path = entry.path();
+            if path.is_dir() && self.get_instance_id(&path) == Some(instance_id) {
+                return Ok(path);
+            }
+        }
+
+        match current.as_ref().and_then(|root| self.get_instance_id(root)) {
+            Some(other) => anyhow::bail!(
+                "Project at {} is configured as instance {}, not {}.",
+                current.unwrap().display(),
+                other,
+                instance_id
+            ),
+            None => anyhow::bail!(
+                "No bundle configured as instance {} found. Run 'configure {}' first.",
+                instance_id,
+                instance_id
+            ),
+        }
+    }
+
+    fn known_instance_ids(&self) -> Vec<u16> {
+        let current = self
+            .find_root()
+            .ok()
+            .map(|root| self.get_bundles_dir(&root));
+        let mut ids: Vec<u16> = fs::read_dir(&self.current_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && Some(self.get_bundles_dir(path)) != current)
+            .filter_map(|path| self.get_instance_id(&path))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+/// A directory is a Liferay Workspace if its settings.gradle applies the workspace plugin
+fn is_liferay_workspace(path: &Path) -> bool {
+    ["settings.gradle", "settings.gradle.kts"]
+        .iter()
+        .any(|file| {
+            fs::read_to_string(path.join(file))
+                .map(|content| content.contains("com.liferay.workspace"))
+                .unwrap_or(false)
+        })
 }
 
 #[cfg(test)]
@@ -146,9 +302,112 @@ mod tests {
 
         let project = LiferayProject {
             current_dir: dir.path().to_path_buf(),
+            tomcat: None,
+            prompt_tomcat: false,
         };
 
         let found_tomcat = project.find_tomcat(dir.path()).unwrap();
         assert!(found_tomcat.to_string_lossy().contains("tomcat-9.0.90"));
     }
+
+    #[test]
+    fn test_workspace_home_dir_override() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("settings.gradle"),
+            "apply plugin: \"com.liferay.workspace\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("gradle.properties"),
+            "liferay.workspace.home.dir=bundles-dxp\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("gradle-local.properties"),
+            "liferay.workspace.home.dir=../bundles-local\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("modules/foo")).unwrap();
+
+        let project = LiferayProject {
+            current_dir: dir.path().join("modules/foo"),
+            tomcat: None,
+            prompt_tomcat: false,
+        };
+
+        let root = project.find_root().unwrap();
+        assert_eq!(root, dir.path());
+        assert_eq!(project.detect_type(&root), ProjectType::LiferayWorkspace);
+        assert_eq!(
+            project.get_workspace_home(&root),
+            dir.path().join("../bundles-local")
+        );
+    }
+
+    #[test]
+    fn test_find_tomcat_candidates_newest_first() {
+        let dir = tempdir().unwrap();
+        let bundles_path = dir.path().join("bundles");
+        fs::create_dir_all(bundles_path.join("tomcat-9.0.80")).unwrap();
+        fs::create_dir_all(bundles_path.join("tomcat-9.0.90")).unwrap();
+        fs::create_dir_all(bundles_path.join("tomcat-9.0.100")).unwrap();
+
+        let project = LiferayProject {
+            current_dir: dir.path().to_path_buf(),
+            tomcat: None,
+            prompt_tomcat: false,
+        };
+
+        let candidates = project.find_tomcat_candidates(dir.path()).unwrap();
+        assert!(candidates[0].ends_with("tomcat-9.0.100"));
+        assert!(candidates[2].ends_with("tomcat-9.0.80"));
+
+        // Liferay's own `.liferay-home` marker is empty and does not pick a Tomcat
+        fs::write(bundles_path.join(".liferay-home"), "").unwrap();
+        let found_tomcat = project.find_tomcat(dir.path()).unwrap();
+        assert!(found_tomcat.ends_with("tomcat-9.0.100"));
+
+        let chosen = LiferayProject {
+            tomcat: Some(PathBuf::from("tomcat-9.0.90")),
+            ..project
+        };
+        assert!(chosen
+            .find_tomcat(dir.path())
+            .unwrap()
+            .ends_with("tomcat-9.0.90"));
+    }
+
+    #[test]
+    fn test_known_instance_ids_leave_out_current_project() {
+        let dir = tempdir().unwrap();
+        for (name, id) in [("bundle-a", 1), ("bundle-b", 2)] {
+            let bundle = dir.path().join(name);
+            fs::create_dir_all(bundle.join("osgi")).unwrap();
+            fs::create_dir_all(bundle.join("deploy")).unwrap();
+            fs::write(
+                bundle.join("portal-ext.properties"),
+                format!("session.cookie.name=LFR_SESSION_{}\n", id),
+            )
+            .unwrap();
+        }
+
+        let outside = LiferayProject {
+            current_dir: dir.path().to_path_buf(),
+            tomcat: None,
+            prompt_tomcat: false,
+        };
+        assert_eq!(outside.known_instance_ids(), vec![1, 2]);
+
+        let inside = LiferayProject {
+            current_dir: dir.path().join("bundle-a"),
+            tomcat: None,
+            prompt_tomcat: false,
+        };
+        assert_eq!(
+            inside.get_instance_id(&inside.find_root().unwrap()),
+            Some(1)
+        );
+        assert!(inside.known_instance_ids().is_empty());
+    }
 }
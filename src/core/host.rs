@@ -0,0 +1,90 @@
+use crate::core::PropertiesDocument;
+use anyhow::Result;
+
+/// portal-ext.properties keys written by `configure --host`
+pub const HOST_KEYS: &[&str] = &[
+    "web.server.host",
+    "virtual.hosts.valid.hosts",
+    "company.default.virtual.host.name",
+    "redirect.url.domains.allowed",
+];
+
+/// Host names Liferay accepts out of the box; kept valid so `localhost` still works
+const LOOPBACK_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[0:0:0:0:0:0:0:1]", "[::1]"];
+
+/// Rejects anything that is not a bare host name (no scheme, port or path)
+pub fn validate_host(host: &str) -> Result<()> {
+    let valid = !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid {
+        anyhow::bail!(
+            "'{}' is not a valid host name (expected something like app2.localhost)",
+            host
+        );
+    }
+    Ok(())
+}
+
+/// Serves the default company on `host`, keeping the loopback names valid
+pub fn apply_host(props: &mut PropertiesDocument, host: &str) {
+    let mut valid_hosts = vec![host];
+    valid_hosts.extend(LOOPBACK_HOSTS.iter().filter(|h| **h != host));
+    props.set("web.server.host", host);
+    props.set("virtual.hosts.valid.hosts", &valid_hosts.join(","));
+    props.set("company.default.virtual.host.name", host);
+    props.set("redirect.url.domains.allowed", host);
+}
+
+/// Host name the instance is served on: `web.server.host`, or `localhost`
+pub fn instance_host(props: &PropertiesDocument) -> &str {
+    props
+        .get("web.server.host")
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+        .unwrap_or("localhost")
+}
+
+/// Browser URL of an instance, leaving out the scheme's default port
+pub fn instance_url(props: &PropertiesDocument, https: bool, port: u16) -> String {
+    let (scheme, default_port) = if https { ("https", 443) } else { ("http", 80) };
+    if port == default_port {
+        format!("{}://{}", scheme, instance_host(props))
+    } else {
+        format!("{}://{}:{}", scheme, instance_host(props), port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_host_and_url() {
+        let mut props = PropertiesDocument::parse("session.cookie.name=LFR_SESSION_2\n");
+        assert_eq!(instance_url(&props, false, 8280), "http://localhost:8280");
+
+        apply_host(&mut props, "app2.localhost");
+        assert_eq!(
+            props.get("virtual.hosts.valid.hosts"),
+            Some("app2.localhost,localhost,127.0.0.1,[0:0:0:0:0:0:0:1],[::1]")
+        );
+        assert_eq!(
+            props.get("company.default.virtual.host.name"),
+            Some("app2.localhost")
+        );
+        assert_eq!(
+            instance_url(&props, true, 8643),
+            "https://app2.localhost:8643"
+        );
+        assert_eq!(instance_url(&props, true, 443), "https://app2.localhost");
+
+        assert!(validate_host("app2.localhost").is_ok());
+        assert!(validate_host("http://app2.localhost").is_err());
+        assert!(validate_host("app2.localhost:8280").is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use crate::core::config::read_json;
+use crate::core::tomcat::{classify_connectors, ConnectorRole};
+use anyhow::Result;
+use edit_xml::{Document, Element};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Password of the generated keystore and its key. The certificate is self-signed
+/// and only ever used on this machine.
+pub const KEYSTORE_PASSWORD: &str = "changeit";
+const KEY_ALIAS: &str = "lfr-local";
+/// Where the keystore lives inside the Tomcat directory, so it travels with exports
+const KEYSTORE_FILE: &str = "conf/lfr-local.p12";
+
+/// What `configure --https` set up, so `reset --https` can undo it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpsRecord {
+    pub keystore: PathBuf,
+    /// Host names in the certificate's subject alternative names
+    pub hosts: Vec<String>,
+    /// `false` when an HTTPS connector already existed and only its certificate was replaced
+    pub connector_added: bool,
+}
+
+/// The generated keystore, kept in Tomcat's `conf`, and its record, kept in
+/// `.lfr-local/https` inside Liferay Home
+pub struct HttpsStore {
+    home: PathBuf,
+    tomcat: PathBuf,
+}
+
+impl HttpsStore {
+    pub fn new(home: &Path, tomcat: &Path) -> Self {
+        HttpsStore {
+            home: home.to_path_buf(),
+            tomcat: tomcat.to_path_buf(),
+        }
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.home.join(".lfr-local/https")
+    }
+
+    pub fn keystore(&self) -> PathBuf {
+        self.tomcat.join(KEYSTORE_FILE)
+    }
+
+    /// The keystore as server.xml refers to it, independent of where the bundle lives
+    pub fn keystore_reference() -> String {
+        format!("${{catalina.base}}/{}", KEYSTORE_FILE)
+    }
+
+    /// Where a new keystore is generated before the change set swaps it in
+    pub fn staged_keystore(&self) -> PathBuf {
+        self.dir().join("keystore.p12.new")
+    }
+
+    pub fn record_path(&self) -> PathBuf {
+        self.dir().join("https.json")
+    }
+
+    pub fn load(&self) -> Option<HttpsRecord> {
+        read_json(&self.record_path()).ok()
+    }
+
+    /// Whether the keystore on disk already covers `hosts`
+    pub fn is_current(&self, hosts: &[String]) -> bool {
+        self.keystore().exists() && self.load().is_some_and(|r| r.hosts == hosts)
+    }
+
+    /// Generates a self-signed certificate for `hosts` (and 127.0.0.1) into a fresh
+    /// PKCS12 keystore at `staged_keystore` with the JDK's `keytool`
+    pub fn generate(&self, keytool: &Path, hosts: &[String]) -> Result<()> {
+        let keystore = self.staged_keystore();
+        fs::create_dir_all(self.dir())?;
+        if keystore.exists() {
+            fs::remove_file(&keystore)?;
+        }
+        let output = Command::new(keytool)
+            .args(["-genkeypair", "-alias", KEY_ALIAS, "-keyalg", "RSA"])
+            .args([
+                "-keysize",
+                "2048",
+                "-validity",
+                "825",
+                "-storetype",
+                "PKCS12",
+            ])
+            .arg("-keystore")
+            .arg(&keystore)
+            .args([
+                "-storepass",
+                KEYSTORE_PASSWORD,
+                "-keypass",
+                KEYSTORE_PASSWORD,
+            ])
+            .arg("-dname")
+            .arg(format!(
+                "CN={}, O=lfr-local",
+                hosts.first().map(String::as_str).unwrap_or("localhost")
+            ))
+            .arg("-ext")
+            .arg(subject_alt_names(hosts))
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", keytool.display(), e))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "keytool failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// `-ext` argument listing every host name plus the loopback address
+fn subject_alt_names(hosts: &[String]) -> String {
+    let mut names: Vec<String> = hosts.iter().map(|h| format!("dns:{}", h)).collect();
+    names.push("ip:127.0.0.1".to_string());
+    format!("SAN={}", names.join(","))
+}
+
+fn certificate_attributes(keystore: &str) -> [(&'static str, String); 3] {
+    [
+        ("certificateKeystoreFile", keystore.to_string()),
+        ("certificateKeystorePassword", KEYSTORE_PASSWORD.to_string()),
+        ("certificateKeystoreType", "PKCS12".to_string()),
+    ]
+}
+
+/// Points the HTTPS connector at `keystore` on `port`, adding the connector next to
+/// the HTTP one if server.xml has none. Returns whether a connector was added.
+pub fn enable_connector(doc: &mut Document, port: u16, keystore: &str) -> Result<bool> {
+    let connectors = classify_connectors(doc);
+    let existing = connectors
+        .iter()
+        .find(|(role, _)| *role == ConnectorRole::Https)
+        .map(|(_, c)| *c);
+    let connector = match existing {
+        Some(connector) => connector,
+        None => {
+            let (_, anchor) = connectors
+                .iter()
+                .find(|(role, _)| *role == ConnectorRole::Http)
+                .or(connectors.first())
+                .ok_or_else(|| anyhow::anyhow!("server.xml has no Connector"))?;
+            let service = anchor
+                .parent(doc)
+                .ok_or_else(|| anyhow::anyhow!("server.xml Connector has no Service"))?;
+            let index = service
+                .children(doc)
+                .iter()
+                .position(|n| n.as_element() == Some(*anchor))
+                .map_or(0, |i| i + 1);
+            let connector = Element::build("Connector")
+                .attribute("protocol", "org.apache.coyote.http11.Http11NioProtocol")
+                .attribute("maxThreads", "150")
+                .finish(doc);
+            service
+                .insert_child(doc, index, connector.into())
+                .map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+            connector
+        }
+    };
+    connector.set_attribute(doc, "port", port.to_string());
+    connector.set_attribute(doc, "SSLEnabled", "true");
+    connector.set_attribute(doc, "scheme", "https");
+    connector.set_attribute(doc, "secure", "true");
+
+    // Connector-level keystore attributes are the pre-SSLHostConfig style;
+    // Tomcat refuses a connector that mixes both
+    if connector.attribute(doc, "keystoreFile").is_some() {
+        connector.set_attribute(doc, "keystoreFile", keystore);
+        connector.set_attribute(doc, "keystorePass", KEYSTORE_PASSWORD);
+        connector.set_attribute(doc, "keystoreType", "PKCS12");
+        return Ok(existing.is_none());
+    }
+
+    let host_config = match connector.find(doc, "SSLHostConfig") {
+        Some(host_config) => host_config,
+        None => Element::build("SSLHostConfig").push_to(doc, connector),
+    };
+    let certificate = match host_config.find(doc, "Certificate") {
+        Some(certificate) => certificate,
+        None => Element::build("Certificate")
+            .attribute("type", "RSA")
+            .push_to(doc, host_config),
+    };
+    for (name, value) in certificate_attributes(keystore) {
+        certificate.set_attribute(doc, name, value);
+    }
+    Ok(existing.is_none())
+}
+
+/// Undoes `enable_connector`: removes an added connector, or puts a pre-existing one
+/// back the way it is in `baseline`, apart from its port (removing it if the baseline has none)
+pub fn disable_connector(
+    doc: &mut Document,
+    record: &HttpsRecord,
+    baseline: Option<&Document>,
+) -> Result<()> {
+    let Some(connector) = classify_connectors(doc)
+        .into_iter()
+        .find(|(role, _)| *role == ConnectorRole::Https)
+        .map(|(_, c)| c)
+    else {
+        return Ok(());
+    };
+    let original = baseline.and_then(|b| {
+        classify_connectors(b)
+            .into_iter()
+            .find(|(role, _)| *role == ConnectorRole::Https)
+            .map(|(_, c)| (b, c))
+    });
+    match original {
+        Some((baseline, original)) if !record.connector_added => {
+            // The port stays as configured; `reset --ports` owns that
+            let port = connector.attribute(doc, "port").map(str::to_string);
+            *connector.mut_attributes(doc) = original.attributes(baseline).clone();
+            if let Some(port) = port {
+                connector.set_attribute(doc, "port", port);
+            }
+            connector.clear_children(doc);
+            for child in original.child_elements(baseline) {
+                copy_element(baseline, child, doc, connector)?;
+            }
+        }
+        _ => connector
+            .detach(doc)
+            .map_err(|e| anyhow::anyhow!("XML Error: {}", e))?,
+    }
+    Ok(())
+}
+
+/// Deep-copies an element (attributes and child elements) between documents
+fn copy_element(
+    from: &Document,
+    element: Element,
+    to: &mut Document,
+    parent: Element,
+) -> Result<()> {
+    let copy = Element::new(to, element.full_name(from));
+    *copy.mut_attributes(to) = element.attributes(from).clone();
+    parent
+        .push_child(to, copy)
+        .map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+    for child in element.child_elements(from) {
+        copy_element(from, child, to, copy)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::tomcat::write_xml;
+
+    const SERVER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Server port="8005"><Service name="Catalina">
+<Connector port="8080" protocol="HTTP/1.1" redirectPort="8443"/>
+<Connector port="8009" protocol="AJP/1.3" redirectPort="8443"/>
+<Engine name="Catalina"/>
+</Service></Server>"#;
+
+    #[test]
+    fn test_connector_is_added_and_removed() {
+        let keystore = HttpsStore::keystore_reference();
+        let keystore = keystore.as_str();
+        let mut doc = Document::parse_str(SERVER_XML).unwrap();
+        assert!(enable_connector(&mut doc, 8643, keystore).unwrap());
+        // A second run updates the connector it added instead of adding another
+        assert!(!enable_connector(&mut doc, 8643, keystore).unwrap());
+
+        let doc = Document::parse_str(&write_xml(&doc).unwrap()).unwrap();
+        let roles = classify_connectors(&doc);
+        assert_eq!(roles.len(), 3);
+        let (role, https) = roles[1];
+        assert_eq!(role, ConnectorRole::Https);
+        assert_eq!(https.attribute(&doc, "port"), Some("8643"));
+        let certificate = https
+            .find(&doc, "SSLHostConfig")
+            .and_then(|h| h.find(&doc, "Certificate"))
+            .unwrap();
+        assert_eq!(
+            certificate.attribute(&doc, "certificateKeystoreFile"),
+            Some("${catalina.base}/conf/lfr-local.p12")
+        );
+
+        let record = HttpsRecord {
+            keystore: PathBuf::from("/home/me/bundles/tomcat/conf/lfr-local.p12"),
+            hosts: vec!["localhost".to_string()],
+            connector_added: true,
+        };
+        let mut doc = doc;
+        disable_connector(&mut doc, &record, None).unwrap();
+        assert_eq!(classify_connectors(&doc).len(), 2);
+    }
+
+    #[test]
+    fn test_existing_connector_is_restored_from_baseline() {
+        let baseline = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Server port="8005"><Service name="Catalina">
+<Connector port="8443" protocol="HTTP/1.1" SSLEnabled="true">
+<SSLHostConfig><Certificate certificateKeystoreFile="conf/mine.jks" type="RSA"/></SSLHostConfig>
+</Connector>
+</Service></Server>"#;
+        let baseline = Document::parse_str(baseline).unwrap();
+        let mut doc = Document::parse_str(&write_xml(&baseline).unwrap()).unwrap();
+        assert!(!enable_connector(&mut doc, 8543, "/tmp/keystore.p12").unwrap());
+
+        let record = HttpsRecord {
+            keystore: PathBuf::from("/tmp/keystore.p12"),
+            hosts: vec!["localhost".to_string()],
+            connector_added: false,
+        };
+        disable_connector(&mut doc, &record, Some(&baseline)).unwrap();
+        let (_, connector) = classify_connectors(&doc)[0];
+        assert_eq!(connector.attribute(&doc, "port"), Some("8543"));
+        assert_eq!(connector.attribute(&doc, "scheme"), None);
+        let certificate = connector
+            .find(&doc, "SSLHostConfig")
+            .and_then(|h| h.find(&doc, "Certificate"))
+            .unwrap();
+        assert_eq!(
+            certificate.attribute(&doc, "certificateKeystoreFile"),
+            Some("conf/mine.jks")
+        );
+        assert_eq!(
+            subject_alt_names(&record.hosts),
+            "SAN=dns:localhost,ip:127.0.0.1"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+/// What the generated run configurations need to know about an instance
+pub struct IdeInstance {
+    pub instance_id: u16,
+    pub debug_port: u16,
+    /// Tomcat `bin` directory, relative to the workspace root where possible
+    pub tomcat_bin: String,
+}
+
+impl IdeInstance {
+    fn script(&self) -> &'static str {
+        if cfg!(windows) {
+            "catalina.bat"
+        } else {
+            "catalina.sh"
+        }
+    }
+
+    pub fn start_name(&self) -> String {
+        format!("Start Liferay Instance {}", self.instance_id)
+    }
+
+    pub fn attach_name(&self) -> String {
+        format!("Attach to Liferay Instance {}", self.instance_id)
+    }
+
+    /// IntelliJ run configuration files (name under `.idea/runConfigurations`, contents)
+    pub fn intellij(&self) -> Vec<(String, String)> {
+        let bin = join_path("$PROJECT_DIR$", &self.tomcat_bin);
+        let start = format!(
+            r#"<component name="ProjectRunConfigurationManager">
+  <configuration default="false" name="{name}" type="ShConfigurationType">
+    <option name="SCRIPT_TEXT" value="" />
+    <option name="INDEPENDENT_SCRIPT_PATH" value="true" />
+    <option name="SCRIPT_PATH" value="{script}" />
+    <option name="SCRIPT_OPTIONS" value="jpda start" />
+    <option name="INDEPENDENT_SCRIPT_WORKING_DIRECTORY" value="true" />
+    <option name="SCRIPT_WORKING_DIRECTORY" value="{bin}" />
+    <option name="INDEPENDENT_INTERPRETER_PATH" value="true" />
+    <option name="INTERPRETER_PATH" value="" />
+    <option name="INTERPRETER_OPTIONS" value="" />
+    <option name="EXECUTE_IN_TERMINAL" value="true" />
+    <option name="EXECUTE_SCRIPT_FILE" value="true" />
+    <envs />
+    <method v="2" />
+  </configuration>
+</component>
+"#,
+            name = xml_escape(&self.start_name()),
+            script = xml_escape(&join_path(&bin, self.script())),
+            bin = xml_escape(&bin),
+        );
+        let attach = format!(
+            r#"<component name="ProjectRunConfigurationManager">
+  <configuration default="false" name="{name}" type="Remote">
+    <option name="USE_SOCKET_TRANSPORT" value="true" />
+    <option name="SERVER_MODE" value="false" />
+    <option name="SHMEM_ADDRESS" />
+    <option name="HOST" value="localhost" />
+    <option name="PORT" value="{port}" />
+    <option name="AUTO_RESTART" value="false" />
+    <RunnerSettings RunnerId="Debug">
+      <option name="DEBUG_PORT" value="{port}" />
+      <option name="LOCAL" value="false" />
+    </RunnerSettings>
+    <method v="2" />
+  </configuration>
+</component>
+"#,
+            name = xml_escape(&self.attach_name()),
+            port = self.debug_port,
+        );
+        vec![
+            (file_name(&self.start_name()), start),
+            (file_name(&self.attach_name()), attach),
+        ]
+    }
+
+    /// Adds (or replaces) this instance's attach configuration in `.vscode/launch.json`
+    pub fn merge_vscode_launch(&self, existing: &str) -> Result<String> {
+        let entry = json!({
+            "type": "java",
+            "name": self.attach_name(),
+            "request": "attach",
+            "hostName": "localhost",
+            "port": self.debug_port,
+        });
+        merge_named(existing, "configurations", "name", entry, json!("0.2.0"))
+    }
+
+    /// Adds (or replaces) this instance's start task in `.vscode/tasks.json`
+    pub fn merge_vscode_tasks(&self, existing: &str) -> Result<String> {
+        let bin = join_path("${workspaceFolder}", &self.tomcat_bin);
+        let entry = json!({
+            "label": self.start_name(),
+            "type": "shell",
+            "command": join_path(&bin, self.script()),
+            "args": ["jpda", "start"],
+            "options": { "cwd": bin },
+            "problemMatcher": [],
+        });
+        merge_named(existing, "tasks", "label", entry, json!("2.0.0"))
+    }
+}
+
+/// Inserts `entry` into the array `list` of a VS Code JSON file, replacing the
+/// element with the same `key`. Other entries and settings are kept.
+fn merge_named(
+    existing: &str,
+    list: &str,
+    key: &str,
+    entry: Value,
+    version: Value,
+) -> Result<String> {
+    let mut document: Value = if existing.trim().is_empty() {
+        json!({ "version": version })
+    } else {
+        serde_json::from_str(&strip_json_comments(existing))
+            .map_err(|e| anyhow::anyhow!("Cannot merge into existing file: {}", e))?
+    };
+    let object = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("Cannot merge into existing file: not a JSON object"))?;
+    let entries = object
+        .entry(list)
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or_else(|| {
+            anyhow::anyhow!("Cannot merge into existing file: '{}' is not a list", list)
+        })?;
+
+    match entries.iter_mut().find(|e| e.get(key) == entry.get(key)) {
+        Some(current) => *current = entry,
+        None => entries.push(entry),
+    }
+    Ok(format!("{}\n", serde_json::to_string_pretty(&document)?))
+}
+
+/// VS Code files are JSON with comments; drop `//` and `/* */` comments outside strings
+fn strip_json_comments(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn join_path(base: &str, relative: &str) -> String {
+    if relative.is_empty() {
+        base.to_string()
+    } else if relative.starts_with('/') || relative.contains(':') {
+        relative.to_string()
+    } else {
+        format!("{}/{}", base, relative)
+    }
+}
+
+fn file_name(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}.xml", safe)
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vscode_launch_merge_keeps_other_entries() {
+        let instance = IdeInstance {
+            instance_id: 2,
+            debug_port: 8200,
+            tomcat_bin: "bundles/tomcat-9.0.90/bin".to_string(),
+        };
+        let existing = r#"{
+  // user comment
+  "version": "0.2.0",
+  "configurations": [
+    { "type": "node", "name": "Frontend", "url": "http://x//y" },
+    { "type": "java", "name": "Attach to Liferay Instance 2", "port": 8000 }
+  ]
+}"#;
+        let merged: Value =
+            serde_json::from_str(&instance.merge_vscode_launch(existing).unwrap()).unwrap();
+        let configurations = merged["configurations"].as_array().unwrap();
+        assert_eq!(configurations.len(), 2);
+        assert_eq!(configurations[0]["url"], "http://x//y");
+        assert_eq!(configurations[1]["port"], 8200);
+
+        let tasks: Value = serde_json::from_str(&instance.merge_vscode_tasks("").unwrap()).unwrap();
+        assert_eq!(
+            tasks["tasks"][0]["options"]["cwd"],
+            "${workspaceFolder}/bundles/tomcat-9.0.90/bin"
+        );
+        assert_eq!(instance.intellij()[1].0, "Attach_to_Liferay_Instance_2.xml");
+    }
+}
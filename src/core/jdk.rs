@@ -0,0 +1,167 @@
+use crate::core::PropertiesDocument;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An installed JDK, described by the `release` file in its home directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jdk {
+    pub home: PathBuf,
+    /// `JAVA_VERSION`, e.g. "17.0.9" or "1.8.0_392"
+    pub version: String,
+    /// `IMPLEMENTOR`, e.g. "Eclipse Adoptium"
+    pub vendor: Option<String>,
+}
+
+impl Jdk {
+    /// Reads `<home>/release`; `None` if the directory is not a JDK
+    pub fn from_home(home: &Path) -> Option<Jdk> {
+        let release = PropertiesDocument::parse(&fs::read_to_string(home.join("release")).ok()?);
+        let value = |key: &str| {
+            release
+                .get(key)
+                .map(|v| v.trim().trim_matches('"').to_string())
+        };
+        Some(Jdk {
+            home: home.to_path_buf(),
+            version: value("JAVA_VERSION")?,
+            vendor: value("IMPLEMENTOR"),
+        })
+    }
+
+    pub fn major(&self) -> u16 {
+        major_version(&self.version)
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.vendor {
+            Some(vendor) => format!("{} ({})", self.version, vendor),
+            None => self.version.clone(),
+        }
+    }
+}
+
+/// Feature release number: 8 for "1.8.0_392", 17 for "17.0.9"
+pub fn major_version(version: &str) -> u16 {
+    let mut parts = version.split(['.', '_', '+', '-']);
+    match parts.next().and_then(|p| p.parse().ok()) {
+        Some(1) => parts.next().and_then(|p| p.parse().ok()).unwrap_or(1),
+        Some(major) => major,
+        None => 0,
+    }
+}
+
+/// The JDK an instance runs on and where it was found: the `JAVA_HOME` pinned in
+/// setenv.sh, else the `JAVA_HOME` environment variable, else `java` on the `PATH`.
+/// `None` if a pinned JDK does not exist.
+pub fn active(pinned: Option<&Path>) -> Option<(Jdk, &'static str)> {
+    if let Some(home) = pinned {
+        return Jdk::from_home(home).map(|jdk| (jdk, "pinned in setenv.sh"));
+    }
+    if let Some(jdk) = std::env::var_os("JAVA_HOME").and_then(|h| Jdk::from_home(Path::new(&h))) {
+        return Some((jdk, "JAVA_HOME"));
+    }
+    path_java_home()
+        .and_then(|home| Jdk::from_home(&home))
+        .map(|jdk| (jdk, "PATH"))
+}
+
+/// Home of the `java` executable on the `PATH`, following alternatives symlinks
+fn path_java_home() -> Option<PathBuf> {
+    let java = if cfg!(windows) { "java.exe" } else { "java" };
+    let executable = std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(java))
+        .find(|path| path.is_file())?;
+    let bin = fs::canonicalize(executable).ok()?.parent()?.to_path_buf();
+    // Java 8 JDKs run java from <home>/jre/bin
+    bin.ancestors()
+        .skip(1)
+        .take(2)
+        .find(|home| home.join("release").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Directories whose children are JDK homes
+fn search_roots() -> Vec<PathBuf> {
+    let mut roots = vec![
+        PathBuf::from("/usr/lib/jvm"),
+        PathBuf::from("/usr/java"),
+        PathBuf::from("/opt/java"),
+    ];
+    if let Ok(home) = std::env::var("HOME") {
+        let home = Path::new(&home);
+        roots.push(home.join(".sdkman/candidates/java"));
+        roots.push(home.join(".asdf/installs/java"));
+        roots.push(home.join(".jdks"));
+        roots.push(home.join("Library/Java/JavaVirtualMachines"));
+    }
+    roots.push(PathBuf::from("/Library/Java/JavaVirtualMachines"));
+    roots
+}
+
+/// JDKs under the usual install locations plus `JAVA_HOME`, newest first
+pub fn discover() -> Vec<Jdk> {
+    let java_home = std::env::var_os("JAVA_HOME").map(PathBuf::from);
+    discover_in(&search_roots(), java_home.as_deref())
+}
+
+pub fn discover_in(roots: &[PathBuf], java_home: Option<&Path>) -> Vec<Jdk> {
+    let candidates = roots
+        .iter()
+        .flat_map(|root| fs::read_dir(root).into_iter().flatten().flatten())
+        .map(|entry| entry.path())
+        // macOS bundles keep the JDK home in Contents/Home
+        .map(|path| {
+            let mac_home = path.join("Contents/Home");
+            if mac_home.is_dir() {
+                mac_home
+            } else {
+                path
+            }
+        })
+        .chain(java_home.map(Path::to_path_buf));
+
+    let mut jdks: Vec<Jdk> = Vec::new();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    for home in candidates {
+        // SDKMAN's `current` and distro alternatives are symlinks to other entries
+        let canonical = fs::canonicalize(&home).unwrap_or_else(|_| home.clone());
+        if seen.contains(&canonical) {
+            continue;
+        }
+        if let Some(jdk) = Jdk::from_home(&home) {
+            seen.push(canonical);
+            jdks.push(jdk);
+        }
+    }
+    jdks.sort_by_key(|jdk| std::cmp::Reverse(jdk.major()));
+    jdks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_discover_reads_release_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("jvm");
+        for (name, version) in [("java-8", "1.8.0_392"), ("temurin-17", "17.0.9")] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(
+                root.join(name).join("release"),
+                format!(
+                    "IMPLEMENTOR=\"Eclipse Adoptium\"\nJAVA_VERSION=\"{}\"\n",
+                    version
+                ),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(root.join("not-a-jdk")).unwrap();
+
+        let jdks = discover_in(std::slice::from_ref(&root), Some(&root.join("temurin-17")));
+        let majors: Vec<u16> = jdks.iter().map(Jdk::major).collect();
+        assert_eq!(majors, vec![17, 8]);
+        assert_eq!(jdks[0].describe(), "17.0.9 (Eclipse Adoptium)");
+    }
+}
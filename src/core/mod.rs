@@ -1,6 +1,33 @@
+pub mod backup;
+pub mod baseline;
 pub mod config;
+pub mod database;
+pub mod doctor;
 pub mod env;
+pub mod host;
+pub mod https;
+pub mod ide;
+pub mod jdk;
+pub mod overrides;
+pub mod ports;
+pub mod properties;
 pub mod resolver;
+pub mod setenv;
+pub mod snapshot;
+pub mod template;
+pub mod tomcat;
+pub mod transfer;
+pub mod version;
+pub mod wizard;
 
+pub use backup::BackupStore;
+pub use baseline::Baseline;
+pub use database::{DatabaseConfig, DatabaseKind};
 pub use env::{LiferayProject, ProjectType, Workspace};
+pub use ports::PortPlan;
+pub use properties::PropertiesDocument;
 pub use resolver::BundleResolver;
+pub use setenv::SetEnv;
+pub use snapshot::SnapshotStore;
+pub use template::TemplateCache;
+pub use version::BundleVersion;
@@ -0,0 +1,246 @@
+use crate::core::wizard::SETUP_WIZARD_FILE;
+use crate::core::PropertiesDocument;
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+const INCLUDE_KEY: &str = "include-and-override";
+
+/// Files in Liferay Home that Liferay loads after its built-in defaults, in order.
+/// Each file's `include-and-override` files are loaded right after it.
+const HOME_FILES: &[&str] = &[
+    "portal-bundle.properties",
+    PORTAL_EXT_FILE,
+    SETUP_WIZARD_FILE,
+];
+
+const PORTAL_EXT_FILE: &str = "portal-ext.properties";
+
+/// One properties file, in the position Liferay loads it
+pub struct PropertiesLayer {
+    pub path: PathBuf,
+    pub props: PropertiesDocument,
+}
+
+/// A key that portal-ext.properties, or a file loaded after it, sets to different values
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub key: String,
+    /// Every file from portal-ext.properties on that sets the key, with its value, in
+    /// load order; the last one wins
+    pub values: Vec<(PathBuf, String)>,
+}
+
+/// How to settle conflicts so a single file defines each key
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ConflictFix {
+    /// Keep the winning value, moved into portal-ext.properties
+    Move,
+    /// Keep the portal-ext.properties value (or the first one), removing later ones
+    Remove,
+}
+
+/// Loads the properties files of Liferay Home in load order. `read` returns a file's
+/// contents, or `None` if it does not exist; a file included twice is loaded once.
+/// portal-ext.properties is always present, empty when the file does not exist.
+pub fn load_layers(home: &Path, read: impl Fn(&Path) -> Option<String>) -> Vec<PropertiesLayer> {
+    let portal_ext = home.join(PORTAL_EXT_FILE);
+    let read_home = |path: &Path| read(path).or_else(|| (path == portal_ext).then(String::new));
+    let mut layers = Vec::new();
+    for name in HOME_FILES {
+        push_layer(&home.join(name), home, &read_home, &mut layers);
+    }
+    layers
+}
+
+fn push_layer(
+    path: &Path,
+    home: &Path,
+    read: &impl Fn(&Path) -> Option<String>,
+    layers: &mut Vec<PropertiesLayer>,
+) {
+    if layers.iter().any(|l| l.path == path) {
+        return;
+    }
+    let Some(content) = read(path) else {
+        return;
+    };
+    let props = PropertiesDocument::parse(&content);
+    let dir = path.parent().unwrap_or(home);
+    let includes: Vec<PathBuf> = props
+        .entries()
+        .filter(|(key, _)| *key == INCLUDE_KEY)
+        .flat_map(|(_, value)| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            let value = value.replace("${liferay.home}", &home.to_string_lossy());
+            dir.join(value)
+        })
+        .collect();
+    layers.push(PropertiesLayer {
+        path: path.to_path_buf(),
+        props,
+    });
+    for include in includes {
+        push_layer(&include, home, read, layers);
+    }
+}
+
+/// Keys set to more than one distinct value by `portal_ext` and the layers loaded after
+/// it, in order of first appearance. Earlier layers (portal-bundle.properties) hold the
+/// bundle's own defaults, which portal-ext.properties is meant to override.
+pub fn find_conflicts(layers: &[PropertiesLayer], portal_ext: &Path) -> Vec<Conflict> {
+    let start = layers
+        .iter()
+        .position(|l| l.path == portal_ext)
+        .unwrap_or(layers.len());
+    let layers = &layers[start..];
+    let mut keys: Vec<&str> = Vec::new();
+    for layer in layers {
+        for (key, _) in layer.props.entries() {
+            if key != INCLUDE_KEY && !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    keys.into_iter()
+        .filter_map(|key| {
+            let values: Vec<(PathBuf, String)> = layers
+                .iter()
+                .filter_map(|l| l.props.get(key).map(|v| (l.path.clone(), v.to_string())))
+                .collect();
+            values
+                .iter()
+                .any(|(_, v)| *v != values[0].1)
+                .then(|| Conflict {
+                    key: key.to_string(),
+                    values,
+                })
+        })
+        .collect()
+}
+
+/// Edits `layers` so each key of `conflicts` is set in a single file, leaving layers
+/// loaded before `portal_ext` alone
+pub fn fix_conflicts(
+    layers: &mut [PropertiesLayer],
+    conflicts: &[Conflict],
+    fix: ConflictFix,
+    portal_ext: &Path,
+) {
+    let start = layers
+        .iter()
+        .position(|l| l.path == portal_ext)
+        .unwrap_or(layers.len());
+    for conflict in conflicts {
+        let (keep, value) = match fix {
+            ConflictFix::Move => (portal_ext, conflict.values.last()),
+            ConflictFix::Remove => (conflict.values[0].0.as_path(), conflict.values.first()),
+        };
+        let Some((_, value)) = value else {
+            continue;
+        };
+        for layer in layers[start..].iter_mut() {
+            if layer.path == keep {
+                layer.props.set(&conflict.key, value);
+            } else {
+                layer.props.remove(&conflict.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_from<'a>(files: &'a [(&str, &str)]) -> impl Fn(&Path) -> Option<String> + 'a {
+        move |path| {
+            files
+                .iter()
+                .find(|(name, _)| Path::new("/home").join(name) == path)
+                .map(|(_, content)| content.to_string())
+        }
+    }
+
+    #[test]
+    fn test_conflicts_follow_load_order() {
+        let files = [
+            (
+                "portal-ext.properties",
+                "include-and-override=portal-developer.properties\njdbc.default.url=jdbc:hsqldb:lportal_2\nsetup.wizard.enabled=false\n",
+            ),
+            (
+                "portal-developer.properties",
+                "theme.css.fast.load=false\nsetup.wizard.enabled=false\n",
+            ),
+            (
+                "portal-setup-wizard.properties",
+                "include-and-override=${liferay.home}/portal-ext.properties\njdbc.default.url=jdbc:hsqldb:lportal\nadmin.email.from.name=Test\n",
+            ),
+        ];
+        let layers = load_layers(Path::new("/home"), read_from(&files));
+        let loaded: Vec<&Path> = layers.iter().map(|l| l.path.as_path()).collect();
+        assert_eq!(
+            loaded,
+            vec![
+                Path::new("/home/portal-ext.properties"),
+                Path::new("/home/portal-developer.properties"),
+                Path::new("/home/portal-setup-wizard.properties"),
+            ]
+        );
+
+        let conflicts = find_conflicts(&layers, Path::new("/home/portal-ext.properties"));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].key, "jdbc.default.url");
+        assert_eq!(conflicts[0].values[1].1, "jdbc:hsqldb:lportal");
+    }
+
+    #[test]
+    fn test_fix_conflicts() {
+        let files = [
+            (
+                "portal-bundle.properties",
+                "jdbc.default.url=jdbc:hsqldb:bundle\nliferay.home=/home\n",
+            ),
+            (
+                "portal-ext.properties",
+                "jdbc.default.url=jdbc:hsqldb:lportal_2\nliferay.home=/home\n",
+            ),
+            (
+                "portal-setup-wizard.properties",
+                "jdbc.default.url=jdbc:hsqldb:lportal\nsetup.wizard.enabled=false\n",
+            ),
+        ];
+        let portal_ext = Path::new("/home/portal-ext.properties");
+
+        let mut removed = load_layers(Path::new("/home"), read_from(&files));
+        let conflicts = find_conflicts(&removed, portal_ext);
+        // portal-bundle.properties is overridden on purpose, not a conflict
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].values[0].0, portal_ext);
+        fix_conflicts(&mut removed, &conflicts, ConflictFix::Remove, portal_ext);
+        assert_eq!(
+            removed[0].props.get("jdbc.default.url"),
+            Some("jdbc:hsqldb:bundle")
+        );
+        assert_eq!(
+            removed[1].props.get("jdbc.default.url"),
+            Some("jdbc:hsqldb:lportal_2")
+        );
+        assert_eq!(removed[2].props.to_string(), "setup.wizard.enabled=false\n");
+
+        let mut moved = load_layers(Path::new("/home"), read_from(&files));
+        fix_conflicts(&mut moved, &conflicts, ConflictFix::Move, portal_ext);
+        assert_eq!(
+            moved[0].props.get("jdbc.default.url"),
+            Some("jdbc:hsqldb:bundle")
+        );
+        assert_eq!(
+            moved[1].props.get("jdbc.default.url"),
+            Some("jdbc:hsqldb:lportal")
+        );
+        assert_eq!(moved[2].props.get("jdbc.default.url"), None);
+        assert!(find_conflicts(&moved, portal_ext).is_empty());
+    }
+}
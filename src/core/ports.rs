@@ -0,0 +1,345 @@
+use crate::core::tomcat::{ConnectorPorts, ConnectorRole, MAX_ADDITIONAL_CONNECTORS};
+use crate::core::PropertiesDocument;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// portal-ext.properties key holding the Gogo shell's address
+pub const GOGO_KEY: &str = "module.framework.properties.osgi.console";
+
+/// OSGi configuration of the Elasticsearch 7 connector, relative to Liferay Home
+pub const ELASTICSEARCH_CONFIG: &str =
+    "osgi/configs/com.liferay.portal.search.elasticsearch7.configuration.ElasticsearchConfiguration.config";
+
+/// Key of the sidecar's HTTP port in `ELASTICSEARCH_CONFIG`
+const ELASTICSEARCH_PORT_KEY: &str = "sidecarHttpPort";
+
+/// Offsets from `PortPlan::base` of every port an instance listens on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PortOffsets {
+    pub http: u16,
+    pub ajp: u16,
+    pub shutdown: u16,
+    pub ssl: u16,
+    pub gogo: u16,
+    pub elasticsearch: u16,
+    pub debug: u16,
+    pub jmx: u16,
+    /// First of `MAX_ADDITIONAL_CONNECTORS` consecutive offsets for extra connectors
+    pub additional: u16,
+}
+
+impl Default for PortOffsets {
+    fn default() -> Self {
+        PortOffsets {
+            http: 80,
+            ajp: 9,
+            shutdown: 5,
+            ssl: 443,
+            gogo: 3311,
+            // Not 1200 (stock 9200 for ID 0): that is a multiple of the stride like the
+            // debug offset, so instance N's Elasticsearch port would be instance N+12's
+            // debug port, and instance 1 would take 9300, Elasticsearch's transport port
+            elasticsearch: 1201,
+            debug: 0,
+            jmx: 1099,
+            // 8050-8054 for ID 0, away from 8081 and the other stock ports
+            additional: 50,
+        }
+    }
+}
+
+impl PortOffsets {
+    /// Every offset with its service name, including each additional connector's
+    fn named(&self) -> Vec<(String, u32)> {
+        let mut named: Vec<(String, u32)> = [
+            ("HTTP", self.http),
+            ("AJP", self.ajp),
+            ("Shutdown", self.shutdown),
+            ("HTTPS", self.ssl),
+            ("Gogo Shell", self.gogo),
+            ("Elasticsearch", self.elasticsearch),
+            ("Debug", self.debug),
+            ("JMX", self.jmx),
+        ]
+        .iter()
+        .map(|(name, offset)| (name.to_string(), *offset as u32))
+        .collect();
+        for n in 0..MAX_ADDITIONAL_CONNECTORS {
+            named.push((
+                format!("Additional #{}", n + 1),
+                self.additional as u32 + n as u32,
+            ));
+        }
+        named
+    }
+}
+
+/// How instance IDs map to ports: `base + offset + id * stride` for every service.
+/// Read from the `ports` section of `~/.liferay/lfr-local/config.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PortPlan {
+    pub base: u16,
+    pub stride: u16,
+    pub offsets: PortOffsets,
+    /// Ports other tools rely on; an instance that would use one is rejected
+    pub reserved: Vec<u16>,
+}
+
+impl Default for PortPlan {
+    fn default() -> Self {
+        PortPlan {
+            base: 8000,
+            stride: 100,
+            offsets: PortOffsets::default(),
+            reserved: Vec::new(),
+        }
+    }
+}
+
+/// Every port of one instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstancePorts {
+    pub http: u16,
+    pub ajp: u16,
+    pub shutdown: u16,
+    pub ssl: u16,
+    pub gogo: u16,
+    pub elasticsearch: u16,
+    pub debug: u16,
+    pub jmx: u16,
+    /// First port of the additional connectors
+    pub additional: u16,
+}
+
+impl InstancePorts {
+    pub fn all(&self) -> [(&'static str, u16); 8] {
+        [
+            ("HTTP", self.http),
+            ("AJP", self.ajp),
+            ("Shutdown", self.shutdown),
+            ("HTTPS", self.ssl),
+            ("Gogo Shell", self.gogo),
+            ("Elasticsearch", self.elasticsearch),
+            ("Debug", self.debug),
+            ("JMX", self.jmx),
+        ]
+    }
+
+    pub fn connectors(&self) -> ConnectorPorts {
+        ConnectorPorts {
+            shutdown: self.shutdown,
+            http: self.http,
+            https: self.ssl,
+            ajp: self.ajp,
+            additional: self.additional,
+        }
+    }
+}
+
+/// Binds the Gogo shell to `port` on the loopback interface
+pub fn set_gogo_port(props: &mut PropertiesDocument, port: u16) {
+    props.set(GOGO_KEY, &format!("localhost:{}", port));
+}
+
+pub fn gogo_port(props: &PropertiesDocument) -> Option<u16> {
+    props.get(GOGO_KEY)?.rsplit(':').next()?.trim().parse().ok()
+}
+
+/// Sets the sidecar's HTTP port in the connector's `.config` file
+pub fn set_elasticsearch_port(config: &mut PropertiesDocument, port: u16) {
+    config.set(ELASTICSEARCH_PORT_KEY, &format!("\"{}\"", port));
+}
+
+/// Forgets the sidecar's HTTP port, so it falls back to `auto`
+pub fn clear_elasticsearch_port(config: &mut PropertiesDocument) {
+    config.remove(ELASTICSEARCH_PORT_KEY);
+}
+
+pub fn elasticsearch_port(config: &PropertiesDocument) -> Option<u16> {
+    config
+        .get(ELASTICSEARCH_PORT_KEY)?
+        .trim_matches('"')
+        .parse()
+        .ok()
+}
+
+impl PortPlan {
+    /// Rejects plans where two services of different instances would share a port
+    pub fn validate(&self) -> Result<()> {
+        if self.stride == 0 {
+            anyhow::bail!("Port plan stride must be greater than 0");
+        }
+        let offsets = self.offsets.named();
+        for (i, (name, offset)) in offsets.iter().enumerate() {
+            for (other, other_offset) in &offsets[i + 1..] {
+                if offset % self.stride as u32 == other_offset % self.stride as u32 {
+                    anyhow::bail!(
+                        "Port plan offsets for {} ({}) and {} ({}) collide: they differ by a multiple of the stride {}",
+                        name,
+                        offset,
+                        other,
+                        other_offset,
+                        self.stride
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Ports for instance `id`, or an error if any of them is above 65535
+    pub fn ports_for(&self, id: u16) -> Result<InstancePorts> {
+        let port = |name: &str, offset: u16| -> Result<u16> {
+            let value = self.base as u32 + offset as u32 + id as u32 * self.stride as u32;
+            u16::try_from(value).map_err(|_| {
+                anyhow::anyhow!(
+                    "Instance {} would need {} port {}, above the maximum of 65535",
+                    id,
+                    name,
+                    value
+                )
+            })
+        };
+        let o = &self.offsets;
+        Ok(InstancePorts {
+            http: port("HTTP", o.http)?,
+            ajp: port("AJP", o.ajp)?,
+            shutdown: port("Shutdown", o.shutdown)?,
+            ssl: port("HTTPS", o.ssl)?,
+            gogo: port("Gogo Shell", o.gogo)?,
+            elasticsearch: port("Elasticsearch", o.elasticsearch)?,
+            debug: port("Debug", o.debug)?,
+            jmx: port("JMX", o.jmx)?,
+            additional: port("Additional connector", o.additional)?,
+        })
+    }
+
+    /// Every port of instance `id`, followed by those of `additional` extra connectors
+    pub fn all_ports(&self, id: u16, additional: u16) -> Result<Vec<(&'static str, u16)>> {
+        let ports = self.ports_for(id)?;
+        let mut all = ports.all().to_vec();
+        for n in 1..=additional {
+            let port = ports.connectors().port_for(ConnectorRole::Additional(n))?;
+            all.push(("Additional connector", port));
+        }
+        Ok(all)
+    }
+
+    /// Planned ports of instance `id`, including those of `additional` extra connectors,
+    /// that are reserved or already taken, per `in_use`
+    pub fn conflicts(
+        &self,
+        id: u16,
+        additional: u16,
+        in_use: impl Fn(u16) -> bool,
+    ) -> Result<Vec<(&'static str, u16)>> {
+        Ok(self
+            .all_ports(id, additional)?
+            .into_iter()
+            .filter(|(_, port)| self.reserved.contains(port) || in_use(*port))
+            .collect())
+    }
+
+    /// Lowest instance ID from 1 up that is not `taken` and has no conflicting port
+    pub fn next_free(
+        &self,
+        taken: &[u16],
+        additional: u16,
+        in_use: impl Fn(u16) -> bool,
+    ) -> Result<(u16, InstancePorts)> {
+        for id in 1..=u16::MAX {
+            let Ok(ports) = self.ports_for(id) else {
+                break;
+            };
+            if taken.contains(&id) {
+                continue;
+            }
+            match self.conflicts(id, additional, &in_use) {
+                Ok(conflicts) if conflicts.is_empty() => return Ok((id, ports)),
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        anyhow::bail!("No free instance ID left within the port plan")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_plan_matches_stock_ports() {
+        let plan = PortPlan::default();
+        plan.validate().unwrap();
+
+        let ports = plan.ports_for(0).unwrap();
+        assert_eq!(
+            (ports.http, ports.ajp, ports.shutdown, ports.ssl, ports.gogo),
+            (8080, 8009, 8005, 8443, 11311)
+        );
+        assert_eq!(ports.elasticsearch, 9201);
+        assert_eq!(plan.ports_for(1).unwrap().elasticsearch, 9301);
+        assert_eq!(plan.ports_for(2).unwrap().http, 8280);
+        assert!(plan.ports_for(600).is_err());
+    }
+
+    #[test]
+    fn test_validate_and_conflicts() {
+        let mut plan = PortPlan {
+            reserved: vec![8181],
+            ..PortPlan::default()
+        };
+        plan.offsets.http = 81;
+        let conflicts = plan.conflicts(1, 0, |port| port == 8109).unwrap();
+        assert_eq!(conflicts, vec![("HTTP", 8181), ("AJP", 8109)]);
+
+        let (id, ports) = plan.next_free(&[2], 0, |port| port == 8109).unwrap();
+        assert_eq!((id, ports.http), (3, 8381));
+
+        // A second HTTP connector listens on 8350 for instance 3
+        let conflicts = plan.conflicts(3, 1, |port| port == 8350).unwrap();
+        assert_eq!(conflicts, vec![("Additional connector", 8350)]);
+        let (id, _) = plan.next_free(&[2], 1, |port| port == 8350).unwrap();
+        assert_eq!(id, 4);
+
+        plan.offsets.debug = 1281;
+        assert!(plan.validate().is_err());
+
+        // AJP inside the additional connectors' range
+        let mut plan = PortPlan::default();
+        plan.offsets.ajp = plan.offsets.additional + 1;
+        assert!(plan.validate().is_err());
+
+        let stock_elasticsearch = PortPlan {
+            offsets: PortOffsets {
+                elasticsearch: 1200,
+                ..PortOffsets::default()
+            },
+            ..PortPlan::default()
+        };
+        assert!(stock_elasticsearch.validate().is_err());
+    }
+
+    #[test]
+    fn test_gogo_and_elasticsearch_ports_round_trip() {
+        let ports = PortPlan::default().ports_for(2).unwrap();
+
+        let mut props = PropertiesDocument::parse("");
+        set_gogo_port(&mut props, ports.gogo);
+        assert_eq!(props.to_string(), format!("{}=localhost:11511\n", GOGO_KEY));
+        assert_eq!(gogo_port(&props), Some(11511));
+
+        let mut config = PropertiesDocument::parse("operationMode=\"SIDECAR\"\n");
+        set_elasticsearch_port(&mut config, ports.elasticsearch);
+        assert_eq!(
+            config.to_string(),
+            "operationMode=\"SIDECAR\"\nsidecarHttpPort=\"9401\"\n"
+        );
+        assert_eq!(elasticsearch_port(&config), Some(9401));
+        clear_elasticsearch_port(&mut config);
+        assert_eq!(elasticsearch_port(&config), None);
+    }
+}
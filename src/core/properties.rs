@@ -0,0 +1,304 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A logical line of a .properties file. `raw` holds the exact source text,
+/// including continuation lines and the line terminator.
+#[derive(Debug, Clone)]
+struct Line {
+    raw: String,
+    entry: Option<Entry>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    key: String,
+    value: String,
+    /// Source text up to and including the separator, reused when the value changes
+    prefix: String,
+}
+
+/// Lossless model of a Java .properties file. Unchanged documents are written
+/// back byte-for-byte; edits only touch the lines of the affected keys.
+#[derive(Debug, Clone, Default)]
+pub struct PropertiesDocument {
+    lines: Vec<Line>,
+    newline: &'static str,
+}
+
+impl PropertiesDocument {
+    pub fn parse(content: &str) -> Self {
+        let newline = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut lines = Vec::new();
+        let mut rest = content;
+
+        while !rest.is_empty() {
+            let first_line = rest.split('\n').next().unwrap_or_default();
+            let comment = is_comment(first_line);
+            let mut end = 0;
+            // Comments never continue; entries continue while a physical line
+            // ends with an odd number of backslashes
+            loop {
+                let next = rest[end..]
+                    .find('\n')
+                    .map(|i| end + i + 1)
+                    .unwrap_or(rest.len());
+                let body = rest[end..next].trim_end_matches(['\r', '\n']);
+                end = next;
+                let trailing = body.len() - body.trim_end_matches('\\').len();
+                if comment || end == rest.len() || trailing.is_multiple_of(2) {
+                    break;
+                }
+            }
+            let raw = &rest[..end];
+            lines.push(Line {
+                raw: raw.to_string(),
+                entry: parse_entry(raw),
+            });
+            rest = &rest[end..];
+        }
+
+        PropertiesDocument { lines, newline }
+    }
+
+    /// Loads a file, treating a missing file as an empty document
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::parse("")),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Returns the effective value of a key (the last occurrence wins, as in Java)
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .filter(|(k, _)| *k == key)
+            .last()
+            .map(|(_, v)| v)
+    }
+
+    /// Iterates over every key/value pair in file order
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines
+            .iter()
+            .filter_map(|l| l.entry.as_ref())
+            .map(|e| (e.key.as_str(), e.value.as_str()))
+    }
+
+    /// Updates the effective occurrence of a key in place, or appends it
+    pub fn set(&mut self, key: &str, value: &str) {
+        let newline = self.newline;
+        if let Some(line) = self
+            .lines
+            .iter_mut()
+            .rev()
+            .find(|l| l.entry.as_ref().is_some_and(|e| e.key == key))
+        {
+            let eol = line_ending(&line.raw);
+            let entry = line.entry.as_mut().unwrap();
+            line.raw = format!("{}{}{}", entry.prefix, escape(value, false), eol);
+            entry.value = value.to_string();
+            return;
+        }
+
+        if let Some(last) = self.lines.last_mut() {
+            if !last.raw.ends_with('\n') {
+                last.raw.push_str(newline);
+            }
+        }
+        let prefix = format!("{}=", escape(key, true));
+        self.lines.push(Line {
+            raw: format!("{}{}{}", prefix, escape(value, false), newline),
+            entry: Some(Entry {
+                key: key.to_string(),
+                value: value.to_string(),
+                prefix,
+            }),
+        });
+    }
+
+    /// Removes every occurrence of a key. Returns whether anything was removed.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.lines.len();
+        self.lines
+            .retain(|l| l.entry.as_ref().is_none_or(|e| e.key != key));
+        before != self.lines.len()
+    }
+}
+
+impl fmt::Display for PropertiesDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            f.write_str(&line.raw)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_comment(raw: &str) -> bool {
+    let trimmed = raw.trim_start();
+    trimmed.starts_with('#') || trimmed.starts_with('!')
+}
+
+fn line_ending(raw: &str) -> &'static str {
+    if raw.ends_with("\r\n") {
+        "\r\n"
+    } else if raw.ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    }
+}
+
+/// Splits a logical line into key, separator and value following java.util.Properties rules
+fn parse_entry(raw: &str) -> Option<Entry> {
+    if is_comment(raw) || raw.trim().is_empty() {
+        return None;
+    }
+
+    let body = raw.trim_end_matches(['\r', '\n']);
+    let start = body.len() - body.trim_start().len();
+    let chars: Vec<(usize, char)> = body
+        .char_indices()
+        .skip_while(|(i, _)| *i < start)
+        .collect();
+
+    let mut key_end = body.len();
+    let mut escaped = false;
+    for &(i, c) in &chars {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' || c.is_whitespace() {
+            key_end = i;
+            break;
+        }
+    }
+
+    // Separator: optional whitespace, at most one '=' or ':', optional whitespace
+    let after_key = &body[key_end..];
+    let mut value_start = key_end + (after_key.len() - after_key.trim_start().len());
+    if body[value_start..].starts_with(['=', ':']) {
+        value_start += 1;
+        let rest = &body[value_start..];
+        value_start += rest.len() - rest.trim_start().len();
+    }
+
+    Some(Entry {
+        key: unescape(&body[start..key_end]),
+        value: unescape(&body[value_start..]),
+        prefix: body[..value_start].to_string(),
+    })
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('f') => out.push('\x0c'),
+            Some('u') => {
+                let hex: String = (0..4).filter_map(|_| chars.next()).collect();
+                if let Some(decoded) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(decoded);
+                }
+            }
+            // Line continuation: skip the terminator and the next line's indentation
+            Some('\r') | Some('\n') => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+fn escape(text: &str, is_key: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\x0c' => out.push_str("\\f"),
+            ' ' if is_key || i == 0 => out.push_str("\\ "),
+            '=' | ':' | '#' | '!' if is_key => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "# Instance settings\r\n\
+        session.cookie.name=LFR_SESSION_1\r\n\
+        session.cookie.name.foo : bar\r\n\
+        include-and-override=a.properties,\\\r\n    b.properties\r\n\
+        greeting=caf\\u00e9\r\n\
+        \r\n\
+        ! legacy comment";
+
+    #[test]
+    fn test_round_trip_is_byte_identical() {
+        let doc = PropertiesDocument::parse(SAMPLE);
+        assert_eq!(doc.to_string(), SAMPLE);
+        assert_eq!(doc.get("session.cookie.name.foo"), Some("bar"));
+        assert_eq!(
+            doc.get("include-and-override"),
+            Some("a.properties,b.properties")
+        );
+        assert_eq!(doc.get("greeting"), Some("café"));
+    }
+
+    #[test]
+    fn test_set_and_remove_only_touch_matching_keys() {
+        let mut doc = PropertiesDocument::parse(SAMPLE);
+        doc.set("session.cookie.name", "LFR_SESSION_2");
+        doc.remove("greeting");
+        doc.set("jdbc.default.url", "jdbc:hsqldb:lportal_2");
+
+        let out = doc.to_string();
+        assert!(out.contains("session.cookie.name=LFR_SESSION_2\r\n"));
+        assert!(out.contains("session.cookie.name.foo : bar\r\n"));
+        assert!(!out.contains("greeting"));
+        assert!(out.ends_with("! legacy comment\r\njdbc.default.url=jdbc:hsqldb:lportal_2\r\n"));
+    }
+
+    #[test]
+    fn test_separators_and_escaped_keys() {
+        let doc = PropertiesDocument::parse("a:1\nb 2\nc\\:d = 3\ne\n");
+        assert_eq!(doc.get("a"), Some("1"));
+        assert_eq!(doc.get("b"), Some("2"));
+        assert_eq!(doc.get("c:d"), Some("3"));
+        assert_eq!(doc.get("e"), Some(""));
+    }
+}
@@ -32,6 +32,38 @@ impl BundleResolver {
         None
     }
 
+    /// Product type and release version of a bundle URL laid out like the CDN,
+    /// `<base>/<version>/liferay-<product>-tomcat-<version>-<build>.zip`
+    pub fn release_from_url(url: &str) -> Option<(&'static str, String)> {
+        let mut segments = url.trim_end_matches('/').rsplit('/');
+        let filename = segments.next()?;
+        let version = segments.next()?;
+        let product_type = if filename.starts_with("liferay-dxp-tomcat-") {
+            "dxp"
+        } else if filename.starts_with("liferay-portal-tomcat-")
+            || filename.starts_with("liferay-ce-portal-tomcat-")
+        {
+            "portal"
+        } else {
+            return None;
+        };
+        filename
+            .contains(version.trim_end_matches("-lts"))
+            .then(|| (product_type, version.to_string()))
+    }
+
+    /// Converts a `liferay.workspace.product` key (e.g. portal-7.4-ga112, dxp-7.4-u92)
+    /// into the CDN naming used by `resolve` (portal-7.4.3.112-ga112, dxp-7.4.13-u92)
+    pub fn normalize_workspace_product(product: &str) -> String {
+        if let Some(ga) = product.strip_prefix("portal-7.4-ga") {
+            return format!("portal-7.4.3.{}-ga{}", ga, ga);
+        }
+        if let Some(update) = product.strip_prefix("dxp-7.4-u") {
+            return format!("dxp-7.4.13-u{}", update);
+        }
+        product.to_string()
+    }
+
     pub fn resolve(product: &str, base_url_override: Option<String>) -> Result<String> {
         let (prefix, default_base) = if product.starts_with("portal-") {
             (
@@ -180,7 +212,8 @@ impl BundleResolver {
         Ok(matches.last().unwrap().to_string())
     }
 
-    fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    /// Compares dotted/dashed version strings numerically where possible
+    pub fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
         let a_parts: Vec<&str> = a.split(['.', '-']).collect();
         let b_parts: Vec<&str> = b.split(['.', '-']).collect();
 
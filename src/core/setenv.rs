@@ -0,0 +1,298 @@
+/// First line of the block lfr-local owns inside `setenv.sh`
+pub const BLOCK_START: &str = "# BEGIN lfr-local (managed block, changes are overwritten)";
+/// Last line of the managed block
+pub const BLOCK_END: &str = "# END lfr-local";
+
+const OPTS_VARIABLES: &[&str] = &["CATALINA_OPTS", "JAVA_OPTS"];
+
+/// Prefix of the JMX options `configure` sets for the instance's JMX port
+const JMX_PREFIX: &str = "-Dcom.sun.management.jmxremote";
+
+/// `tomcat/bin/setenv.sh` split into user content and the lfr-local block.
+/// Everything outside the block is kept byte for byte.
+pub struct SetEnv {
+    before: String,
+    after: String,
+    /// JVM options set by lfr-local
+    pub options: Vec<String>,
+    /// Extra `export NAME=value` lines set by lfr-local
+    pub variables: Vec<(String, String)>,
+}
+
+impl SetEnv {
+    pub fn parse(content: &str) -> Self {
+        let Some(start) = content.find(BLOCK_START) else {
+            return SetEnv {
+                before: content.to_string(),
+                after: String::new(),
+                options: Vec::new(),
+                variables: Vec::new(),
+            };
+        };
+        let block_end = content[start..]
+            .find(BLOCK_END)
+            .map(|i| start + i + BLOCK_END.len())
+            .unwrap_or(content.len());
+        let after_start = content[block_end..]
+            .find('\n')
+            .map(|i| block_end + i + 1)
+            .unwrap_or(content.len());
+
+        let mut options = Vec::new();
+        let mut variables = Vec::new();
+        for line in content[start..block_end].lines() {
+            let line = line.trim().trim_start_matches("export ");
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            if name == "CATALINA_OPTS" {
+                options.extend(split_options(value));
+            } else if !name.starts_with('#') {
+                variables.push((name.to_string(), value.trim_matches('"').to_string()));
+            }
+        }
+
+        SetEnv {
+            before: content[..start].to_string(),
+            after: content[after_start..].to_string(),
+            options,
+            variables,
+        }
+    }
+
+    /// Adds options, replacing any managed option that sets the same thing
+    pub fn set_options<S: AsRef<str>>(&mut self, options: &[S]) {
+        for option in options {
+            let option = option.as_ref();
+            let key = option_key(option);
+            self.options.retain(|o| option_key(o) != key);
+            self.options.push(option.to_string());
+        }
+    }
+
+    /// Sets both the initial and the maximum heap, e.g. `4g`
+    pub fn set_heap(&mut self, size: &str) {
+        self.set_options(&[format!("-Xms{}", size), format!("-Xmx{}", size)]);
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        match self.variables.iter_mut().find(|(n, _)| n == name) {
+            Some(variable) => variable.1 = value.to_string(),
+            None => self.variables.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn remove_variable(&mut self, name: &str) {
+        self.variables.retain(|(n, _)| n != name);
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Port from the managed `JPDA_ADDRESS` (`localhost:8200` or `8200`)
+    pub fn debug_port(&self) -> Option<u16> {
+        self.variable("JPDA_ADDRESS")?
+            .rsplit(':')
+            .next()?
+            .parse()
+            .ok()
+    }
+
+    /// Enables remote JMX on `port` without authentication, for local tools like VisualVM
+    pub fn set_jmx_port(&mut self, port: u16) {
+        self.set_options(&[
+            format!("{}.port={}", JMX_PREFIX, port),
+            format!("{}.rmi.port={}", JMX_PREFIX, port),
+            format!("{}.host=localhost", JMX_PREFIX),
+            format!("{}.authenticate=false", JMX_PREFIX),
+            format!("{}.ssl=false", JMX_PREFIX),
+        ]);
+    }
+
+    pub fn clear_jmx(&mut self) {
+        self.options.retain(|o| !o.starts_with(JMX_PREFIX));
+    }
+
+    pub fn jmx_port(&self) -> Option<u16> {
+        let prefix = format!("{}.port=", JMX_PREFIX);
+        self.options
+            .iter()
+            .find_map(|o| o.strip_prefix(&prefix)?.parse().ok())
+    }
+
+    /// Drops the managed options, keeping the JMX port and managed variables such as
+    /// `JPDA_ADDRESS` and `JAVA_HOME` that other commands own
+    pub fn clear_options(&mut self) {
+        self.options.retain(|o| o.starts_with(JMX_PREFIX));
+    }
+
+    /// Options the JVM ends up with: every `CATALINA_OPTS`/`JAVA_OPTS` assignment in
+    /// the script, in order, where a later option overrides an earlier one with the same key
+    pub fn effective_options(&self) -> Vec<String> {
+        let mut effective: Vec<String> = Vec::new();
+        let script = format!("{}{}", self.before, self.after);
+        let assigned = script.lines().filter_map(|line| {
+            let line = line.trim().trim_start_matches("export ");
+            let (name, value) = line.split_once('=')?;
+            OPTS_VARIABLES.contains(&name).then(|| split_options(value))
+        });
+        for option in assigned.flatten().chain(self.options.iter().cloned()) {
+            let key = option_key(&option);
+            effective.retain(|o| option_key(o) != key);
+            effective.push(option);
+        }
+        effective
+    }
+}
+
+impl std::fmt::Display for SetEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.options.is_empty() && self.variables.is_empty() {
+            return write!(f, "{}{}", self.before, self.after);
+        }
+
+        let mut before = if self.before.is_empty() {
+            "#!/bin/sh\n\n".to_string()
+        } else {
+            self.before.clone()
+        };
+        if !before.ends_with('\n') {
+            before.push('\n');
+        }
+        writeln!(f, "{}{}", before, BLOCK_START)?;
+        for (name, value) in &self.variables {
+            writeln!(f, "export {}=\"{}\"", name, value)?;
+        }
+        if !self.options.is_empty() {
+            let options: Vec<String> = self.options.iter().map(|o| quote_option(o)).collect();
+            writeln!(f, "CATALINA_OPTS=\"$CATALINA_OPTS {}\"", options.join(" "))?;
+        }
+        write!(f, "{}\n{}", BLOCK_END, self.after)
+    }
+}
+
+/// Splits the right-hand side of an options assignment the way the shell does: once
+/// for the assignment itself and once when catalina.sh evaluates the variable. Drops
+/// references to the variable being extended.
+fn split_options(value: &str) -> Vec<String> {
+    let assigned = shlex::split(value)
+        .map(|words| words.join(" "))
+        .unwrap_or_else(|| value.trim_matches('"').to_string());
+    shlex::split(&assigned)
+        .unwrap_or_else(|| assigned.split_whitespace().map(str::to_string).collect())
+        .into_iter()
+        .filter(|o| !o.starts_with('$'))
+        .collect()
+}
+
+/// Quotes an option for use inside the double-quoted `CATALINA_OPTS` assignment,
+/// so a value with spaces stays one option when catalina.sh evaluates it
+fn quote_option(option: &str) -> String {
+    shlex::try_quote(option)
+        .map(|quoted| quoted.into_owned())
+        .unwrap_or_else(|_| option.to_string())
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('`', "\\`")
+}
+
+/// What an option configures, so a later `-Xmx4g` replaces an earlier `-Xmx2g`
+fn option_key(option: &str) -> String {
+    if let Some(property) = option.strip_prefix("-D") {
+        return format!("-D{}", property.split('=').next().unwrap_or_default());
+    }
+    if let Some(flag) = option.strip_prefix("-XX:") {
+        let name = flag.trim_start_matches(['+', '-']);
+        return format!("-XX:{}", name.split('=').next().unwrap_or_default());
+    }
+    for prefix in ["-Xms", "-Xmx", "-Xss", "-Xmn"] {
+        if option.starts_with(prefix) {
+            return prefix.to_string();
+        }
+    }
+    if let Some(agent) = option.strip_prefix("-agentlib:") {
+        return format!("-agentlib:{}", agent.split('=').next().unwrap_or_default());
+    }
+    option.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_managed_block_round_trip() {
+        let script =
+            "#!/bin/sh\nCATALINA_OPTS=\"$CATALINA_OPTS -Xmx2560m -Dfile.encoding=UTF-8\"\n";
+        let mut setenv = SetEnv::parse(script);
+        assert_eq!(setenv.to_string(), script);
+
+        setenv.set_heap("4g");
+        setenv.set_options(&["-Duser.timezone=GMT", "-XX:+UseG1GC"]);
+        setenv.set_options(&["-XX:-UseG1GC"]);
+        let written = setenv.to_string();
+        assert!(written.starts_with(script));
+
+        let parsed = SetEnv::parse(&written);
+        assert_eq!(
+            parsed.options,
+            vec!["-Xms4g", "-Xmx4g", "-Duser.timezone=GMT", "-XX:-UseG1GC"]
+        );
+        assert_eq!(
+            parsed.effective_options(),
+            vec![
+                "-Dfile.encoding=UTF-8",
+                "-Xms4g",
+                "-Xmx4g",
+                "-Duser.timezone=GMT",
+                "-XX:-UseG1GC"
+            ]
+        );
+
+        let mut with_debug = SetEnv::parse(&written);
+        with_debug.set_variable("JPDA_ADDRESS", "localhost:8200");
+        assert_eq!(
+            SetEnv::parse(&with_debug.to_string()).debug_port(),
+            Some(8200)
+        );
+
+        let mut cleared = parsed;
+        cleared.clear_options();
+        assert_eq!(cleared.to_string(), script);
+
+        // `jvm reset` keeps the JMX port that `configure` owns
+        with_debug.set_jmx_port(9299);
+        with_debug.clear_options();
+        let mut reset = SetEnv::parse(&with_debug.to_string());
+        assert_eq!(reset.options.len(), 5);
+        assert_eq!(reset.jmx_port(), Some(9299));
+        assert_eq!(reset.debug_port(), Some(8200));
+        reset.clear_jmx();
+        assert!(reset.options.is_empty());
+    }
+
+    #[test]
+    fn test_quoted_options_stay_whole() {
+        let script = "#!/bin/sh\nCATALINA_OPTS=\"$CATALINA_OPTS -Dfoo=\\\"a b\\\" -Xmx2g\"\n";
+        let mut setenv = SetEnv::parse(script);
+        assert_eq!(setenv.effective_options(), vec!["-Dfoo=a b", "-Xmx2g"]);
+
+        setenv.set_options(&["-XX:OnOutOfMemoryError=kill -9 %p", "-Dfoo=c d"]);
+        let written = setenv.to_string();
+        assert!(written.contains("'-XX:OnOutOfMemoryError=kill -9 %p'"));
+
+        let parsed = SetEnv::parse(&written);
+        assert_eq!(
+            parsed.options,
+            vec!["-XX:OnOutOfMemoryError=kill -9 %p", "-Dfoo=c d"]
+        );
+        assert_eq!(
+            parsed.effective_options(),
+            vec!["-Xmx2g", "-XX:OnOutOfMemoryError=kill -9 %p", "-Dfoo=c d"]
+        );
+    }
+}
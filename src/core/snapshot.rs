@@ -0,0 +1,180 @@
+use crate::utils::archive::{create_tar_gz, extract_tar_gz};
+use crate::utils::{ChangeSet, CommandRunner};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Paths, relative to Liferay Home, that make up an instance's state
+const SNAPSHOT_PATHS: &[&str] = &["data", "osgi/configs", "portal-ext.properties"];
+
+/// A saved snapshot archive
+pub struct Snapshot {
+    pub name: String,
+    pub size: u64,
+    pub created: DateTime<Local>,
+}
+
+/// Compressed copies of instance state kept in `.lfr-local/snapshots` inside Liferay Home
+pub struct SnapshotStore {
+    home: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(home: &Path) -> Self {
+        SnapshotStore {
+            home: home.to_path_buf(),
+        }
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.home.join(".lfr-local/snapshots")
+    }
+
+    fn archive(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            anyhow::bail!("Invalid snapshot name '{}'", name);
+        }
+        Ok(self.dir().join(format!("{}.tar.gz", name)))
+    }
+
+    /// Archives data/, osgi/configs and portal-ext.properties
+    pub fn save(&self, name: &str) -> Result<PathBuf> {
+        let archive = self.archive(name)?;
+        fs::create_dir_all(self.dir())?;
+        create_tar_gz(
+            &self.home,
+            &archive,
+            |relative| {
+                SNAPSHOT_PATHS.iter().any(|included| {
+                    relative.starts_with(included) || Path::new(included).starts_with(relative)
+                })
+            },
+            &[],
+        )
+        .map_err(|e| anyhow::anyhow!(e))?;
+        Ok(archive)
+    }
+
+    /// Where `restore` unpacks a snapshot, next to the live state so it can be renamed in
+    fn staging(&self) -> PathBuf {
+        self.home.join(".lfr-local/restore")
+    }
+
+    /// Unpacks a snapshot and stages swapping it in for the instance state. A damaged
+    /// archive fails here, before anything live is touched. Call `clear_staging` once
+    /// `changes` are applied.
+    pub fn restore(&self, name: &str, changes: &mut ChangeSet) -> Result<()> {
+        let archive = self.archive(name)?;
+        if !archive.exists() {
+            anyhow::bail!("Snapshot '{}' not found", name);
+        }
+        let staging = self.staging();
+        CommandRunner::force_delete(&staging).map_err(|e| anyhow::anyhow!(e))?;
+        fs::create_dir_all(&staging)?;
+        if let Err(e) = extract_tar_gz(&archive, &staging) {
+            CommandRunner::force_delete(&staging).map_err(|e| anyhow::anyhow!(e))?;
+            anyhow::bail!("Snapshot '{}' could not be extracted: {}", name, e);
+        }
+        for path in SNAPSHOT_PATHS {
+            changes.replace(&self.home.join(path), &staging.join(path));
+        }
+        Ok(())
+    }
+
+    /// Deletes what is left of an extracted snapshot
+    pub fn clear_staging(&self) -> Result<()> {
+        CommandRunner::force_delete(&self.staging()).map_err(|e| anyhow::anyhow!(e))
+    }
+
+    pub fn delete(&self, name: &str) -> Result<()> {
+        let archive = self.archive(name)?;
+        if !archive.exists() {
+            anyhow::bail!("Snapshot '{}' not found", name);
+        }
+        fs::remove_file(archive)?;
+        Ok(())
+    }
+
+    /// Lists snapshots, oldest first
+    pub fn list(&self) -> Vec<Snapshot> {
+        let mut snapshots: Vec<Snapshot> = fs::read_dir(self.dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let name = file_name.strip_suffix(".tar.gz")?.to_string();
+                let metadata = entry.metadata().ok()?;
+                Some(Snapshot {
+                    name,
+                    size: metadata.len(),
+                    created: metadata.modified().ok()?.into(),
+                })
+            })
+            .collect();
+        snapshots.sort_by_key(|s| s.created);
+        snapshots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn restore(store: &SnapshotStore, name: &str) -> Result<()> {
+        let mut changes = ChangeSet::new();
+        store.restore(name, &mut changes)?;
+        changes.apply()?;
+        store.clear_staging()
+    }
+
+    #[test]
+    fn test_save_and_restore_round_trip() {
+        let dir = tempdir().unwrap();
+        let home = dir.path();
+        fs::create_dir_all(home.join("data/hypersonic")).unwrap();
+        fs::create_dir_all(home.join("osgi/configs")).unwrap();
+        fs::create_dir_all(home.join("osgi/modules")).unwrap();
+        fs::write(home.join("data/hypersonic/lportal.script"), "v1").unwrap();
+        fs::write(home.join("osgi/modules/custom.jar"), "jar").unwrap();
+        fs::write(home.join("portal-ext.properties"), "a=1\n").unwrap();
+
+        let store = SnapshotStore::new(home);
+        store.save("fresh").unwrap();
+
+        fs::write(home.join("data/hypersonic/lportal.script"), "v2").unwrap();
+        fs::write(home.join("osgi/configs/new.config"), "x").unwrap();
+        restore(&store, "fresh").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(home.join("data/hypersonic/lportal.script")).unwrap(),
+            "v1"
+        );
+        assert!(!home.join("osgi/configs/new.config").exists());
+        assert!(home.join("osgi/modules/custom.jar").exists());
+        assert_eq!(store.list().len(), 1);
+        assert!(store.save("../escape").is_err());
+        assert!(!home.join(".lfr-local/restore").exists());
+    }
+
+    #[test]
+    fn test_damaged_snapshot_keeps_live_state() {
+        let dir = tempdir().unwrap();
+        let home = dir.path();
+        fs::create_dir_all(home.join("data")).unwrap();
+        fs::write(home.join("data/lportal.script"), "live").unwrap();
+
+        let store = SnapshotStore::new(home);
+        fs::create_dir_all(store.dir()).unwrap();
+        fs::write(store.archive("broken").unwrap(), "not a tar.gz").unwrap();
+        assert!(restore(&store, "broken").is_err());
+
+        assert_eq!(
+            fs::read_to_string(home.join("data/lportal.script")).unwrap(),
+            "live"
+        );
+        assert!(!home.join(".lfr-local/restore").exists());
+    }
+}
@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::utils::archive::extract_bundle;
+
+/// Files that lfr-local (or Liferay itself) rewrites in place. These are always
+/// materialised as independent copies so the template is never modified.
+const MUTABLE_FILES: &[&str] = &[
+    "server.xml",
+    "context.xml",
+    "portal-ext.properties",
+    "portal-setup-wizard.properties",
+    "setenv.sh",
+    "setenv.bat",
+    "elasticsearch.yml",
+];
+
+/// Extensions of files that are never written to after extraction and can
+/// safely be shared between bundles via hardlinks.
+const IMMUTABLE_EXTENSIONS: &[&str] = &["jar", "war", "lpkg", "zip", "so", "dll", "exe"];
+
+/// How each file of a materialised bundle was created
+#[derive(Debug, Default)]
+pub struct MaterialiseStats {
+    pub reflinked: usize,
+    pub hardlinked: usize,
+    pub copied: usize,
+}
+
+pub struct TemplateCache;
+
+impl TemplateCache {
+    /// Root of the extracted-bundle cache (~/.liferay/lfr-local/templates)
+    pub fn root() -> Option<PathBuf> {
+        let home = std::env::var("HOME").ok()?;
+        Some(Path::new(&home).join(".liferay/lfr-local/templates"))
+    }
+
+    /// Derives the cache key for a bundle archive from its file name
+    pub fn key_for_archive(filename: &str) -> String {
+        [".zip", ".tar.gz", ".7z"]
+            .iter()
+            .find_map(|ext| filename.strip_suffix(ext))
+            .unwrap_or(filename)
+            .to_string()
+    }
+
+    /// Cache key of a Liferay release, e.g. `dxp-2024.q1.1`, so a bundle resolved with
+    /// `--product` and one downloaded with `--url` share the same template
+    pub fn key_for_release(product_type: &str, version: &str) -> String {
+        format!("{}-{}", product_type, version.trim_end_matches("-lts"))
+    }
+
+    /// Returns the pristine extracted bundle for a key, if one has been cached
+    pub fn find(key: &str) -> Option<PathBuf> {
+        let path = Self::root()?.join(key);
+        path.is_dir().then_some(path)
+    }
+
+    /// Extracts an archive into the cache under the given key
+    pub fn populate(key: &str, archive: &Path) -> Result<PathBuf> {
+        let root = Self::root().context("HOME is not set; cannot locate template cache")?;
+        let target = root.join(key);
+        let partial = root.join(format!("{}.partial", key));
+
+        let _ = fs::remove_dir_all(&partial);
+        fs::create_dir_all(&partial)?;
+        extract_bundle(archive, &partial, true)
+            .map_err(|e| anyhow::anyhow!("Extraction failed: {}", e))?;
+
+        // Rename last so an interrupted extraction never looks like a valid template
+        let _ = fs::remove_dir_all(&target);
+        fs::rename(&partial, &target)?;
+        Ok(target)
+    }
+
+    /// Recreates a cached bundle at `dest` using reflinks, hardlinks or copies
+    pub fn materialise(template: &Path, dest: &Path) -> Result<MaterialiseStats> {
+        let mut stats = MaterialiseStats::default();
+
+        for entry in WalkDir::new(template) {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(template)?;
+            let target = dest.join(relative);
+            let file_type = entry.file_type();
+
+            if file_type.is_dir() {
+                fs::create_dir_all(&target)?;
+            } else if file_type.is_symlink() {
+                copy_symlink(entry.path(), &target)?;
+                stats.copied += 1;
+            } else if is_mutable(relative) {
+                fs::copy(entry.path(), &target)?;
+                stats.copied += 1;
+            } else if reflink_copy::reflink(entry.path(), &target).is_ok() {
+                fs::set_permissions(&target, entry.metadata()?.permissions())?;
+                stats.reflinked += 1;
+            } else if is_immutable(entry.path()) && fs::hard_link(entry.path(), &target).is_ok() {
+                stats.hardlinked += 1;
+            } else {
+                fs::copy(entry.path(), &target)?;
+                stats.copied += 1;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+fn is_mutable(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    MUTABLE_FILES.contains(&name)
+        || path
+            .components()
+            .any(|c| c.as_os_str() == "data" || c.as_os_str() == "conf")
+}
+
+fn is_immutable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMMUTABLE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dest)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dest: &Path) -> Result<()> {
+    fs::copy(src, dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::BundleResolver;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_materialise_keeps_mutable_files_independent() {
+        let dir = tempdir().unwrap();
+        let template = dir.path().join("template");
+        let conf = template.join("tomcat-9.0.90/conf");
+        let lib = template.join("tomcat-9.0.90/lib");
+        fs::create_dir_all(&conf).unwrap();
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(conf.join("server.xml"), "<Server port=\"8005\"/>").unwrap();
+        fs::write(lib.join("portal-kernel.jar"), "jar").unwrap();
+        fs::write(template.join("portal-ext.properties"), "a=b\n").unwrap();
+
+        let dest = dir.path().join("bundle");
+        TemplateCache::materialise(&template, &dest).unwrap();
+
+        let server_xml = dest.join("tomcat-9.0.90/conf/server.xml");
+        fs::write(&server_xml, "<Server port=\"8105\"/>").unwrap();
+        fs::write(dest.join("portal-ext.properties"), "a=c\n").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(conf.join("server.xml")).unwrap(),
+            "<Server port=\"8005\"/>"
+        );
+        assert_eq!(
+            fs::read_to_string(template.join("portal-ext.properties")).unwrap(),
+            "a=b\n"
+        );
+        assert!(dest.join("tomcat-9.0.90/lib/portal-kernel.jar").exists());
+    }
+
+    #[test]
+    fn test_key_for_archive() {
+        assert_eq!(
+            TemplateCache::key_for_archive("liferay-dxp-tomcat-2024.q1.0-lts.zip"),
+            "liferay-dxp-tomcat-2024.q1.0-lts"
+        );
+        assert_eq!(TemplateCache::key_for_archive("bundle.tar.gz"), "bundle");
+    }
+
+    #[test]
+    fn test_url_and_product_share_a_key() {
+        let url = "https://releases-cdn.liferay.com/dxp/2024.q1.1-lts/liferay-dxp-tomcat-2024.q1.1-1711009264.zip";
+        let (product_type, version) = BundleResolver::release_from_url(url).unwrap();
+        assert_eq!(
+            TemplateCache::key_for_release(product_type, &version),
+            TemplateCache::key_for_release("dxp", "2024.q1.1")
+        );
+
+        let url = "https://mirror.local/portal/7.4.3.112-ga112/liferay-ce-portal-tomcat-7.4.3.112-ga112-20240226061339195.tar.gz";
+        assert_eq!(
+            BundleResolver::release_from_url(url),
+            Some(("portal", "7.4.3.112-ga112".to_string()))
+        );
+        // Archives outside a release directory have no version to go by
+        assert_eq!(
+            BundleResolver::release_from_url("https://example.com/files/bundle.zip"),
+            None
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use edit_xml::{Document, Element};
+use std::fs;
+use std::path::Path;
+
+/// Helper to find all Connector elements at any depth in the XML tree, in document order
+pub fn collect_connectors(doc: &Document) -> Vec<Element> {
+    let mut connectors = Vec::new();
+    let mut stack: Vec<Element> = doc.root_element().into_iter().collect();
+
+    while let Some(el) = stack.pop() {
+        if el.name(doc) == "Connector" {
+            connectors.push(el);
+        }
+        for child in el.children(doc).iter().rev() {
+            if let Some(child_el) = child.as_element() {
+                stack.push(child_el);
+            }
+        }
+    }
+    connectors
+}
+
+/// What a connector is for. The first connector of each kind takes the main role;
+/// any further ones are `Additional`, numbered from 1 in document order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectorRole {
+    Http,
+    Https,
+    Ajp,
+    Additional(u16),
+}
+
+impl ConnectorRole {
+    pub fn label(&self) -> String {
+        match self {
+            ConnectorRole::Http => "HTTP".to_string(),
+            ConnectorRole::Https => "HTTPS".to_string(),
+            ConnectorRole::Ajp => "AJP".to_string(),
+            ConnectorRole::Additional(n) => format!("Additional #{}", n),
+        }
+    }
+}
+
+/// Pairs every connector in server.xml with its role
+pub fn classify_connectors(doc: &Document) -> Vec<(ConnectorRole, Element)> {
+    let mut roles: Vec<(ConnectorRole, Element)> = Vec::new();
+    let mut additional = 0;
+    for connector in collect_connectors(doc) {
+        let protocol = connector
+            .attribute(doc, "protocol")
+            .unwrap_or("http/1.1")
+            .to_lowercase();
+        let is_true = |name: &str| {
+            connector
+                .attribute(doc, name)
+                .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+        };
+        let role = if protocol.contains("ajp") {
+            ConnectorRole::Ajp
+        } else if is_true("SSLEnabled")
+            || is_true("secure")
+            || connector.attribute(doc, "scheme") == Some("https")
+        {
+            ConnectorRole::Https
+        } else {
+            ConnectorRole::Http
+        };
+
+        let role = if roles.iter().any(|(r, _)| *r == role) {
+            additional += 1;
+            ConnectorRole::Additional(additional)
+        } else {
+            role
+        };
+        roles.push((role, connector));
+    }
+    roles
+}
+
+/// Number of `Additional` connectors in a Tomcat's server.xml (0 if it cannot be read)
+pub fn additional_connectors(tomcat: &Path) -> u16 {
+    let Some(doc) = fs::read_to_string(tomcat.join("conf/server.xml"))
+        .ok()
+        .and_then(|xml| Document::parse_str(&xml).ok())
+    else {
+        return 0;
+    };
+    classify_connectors(&doc)
+        .iter()
+        .filter(|(role, _)| matches!(role, ConnectorRole::Additional(_)))
+        .count() as u16
+}
+
+/// Most `Additional` connectors an instance can have; the port plan reserves this many
+/// consecutive ports for them
+pub const MAX_ADDITIONAL_CONNECTORS: u16 = 5;
+
+/// Ports assigned to one instance's connectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectorPorts {
+    pub shutdown: u16,
+    pub http: u16,
+    pub https: u16,
+    pub ajp: u16,
+    /// Port of the first `Additional` connector; further ones follow it
+    pub additional: u16,
+}
+
+impl ConnectorPorts {
+    /// Additional connectors take consecutive ports from `additional`
+    pub fn port_for(&self, role: ConnectorRole) -> anyhow::Result<u16> {
+        match role {
+            ConnectorRole::Http => Ok(self.http),
+            ConnectorRole::Https => Ok(self.https),
+            ConnectorRole::Ajp => Ok(self.ajp),
+            ConnectorRole::Additional(n) if n > MAX_ADDITIONAL_CONNECTORS => anyhow::bail!(
+                "server.xml has more than {} additional connectors",
+                MAX_ADDITIONAL_CONNECTORS
+            ),
+            ConnectorRole::Additional(n) => self.additional.checked_add(n - 1).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} connector would need port {} + {}, above the maximum of 65535",
+                    role.label(),
+                    self.additional,
+                    n - 1
+                )
+            }),
+        }
+    }
+
+    /// Rewrites the shutdown port and every connector's port. Non-TLS connectors
+    /// redirect to the HTTPS port.
+    pub fn apply(&self, doc: &mut Document) -> anyhow::Result<()> {
+        if let Some(root) = doc.root_element() {
+            root.set_attribute(doc, "port", self.shutdown.to_string());
+        }
+        for (role, connector) in classify_connectors(doc) {
+            connector.set_attribute(doc, "port", self.port_for(role)?.to_string());
+            if role != ConnectorRole::Https {
+                connector.set_attribute(doc, "redirectPort", self.https.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serialises an edited Tomcat configuration document
+pub fn write_xml(doc: &Document) -> anyhow::Result<String> {
+    let mut output = Vec::new();
+    doc.write(&mut output)
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(String::from_utf8(output)?)
+}
+
+/// Puts the shutdown port and every connector's `port`/`redirectPort` back to the
+/// values of the connector with the same role in `baseline`. Connectors without a
+/// baseline counterpart and all other attributes are left as they are.
+pub fn restore_ports(current: &str, baseline: &str) -> anyhow::Result<String> {
+    let mut doc = Document::parse_str(current).map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+    let original =
+        Document::parse_str(baseline).map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+
+    if let (Some(root), Some(original_root)) = (doc.root_element(), original.root_element()) {
+        if let Some(port) = original_root.attribute(&original, "port") {
+            root.set_attribute(&mut doc, "port", port);
+        }
+    }
+
+    // Paired by role, so connectors are matched the same way `configure` assigned ports
+    let originals = classify_connectors(&original);
+    for (role, connector) in classify_connectors(&doc) {
+        let Some((_, counterpart)) = originals.iter().find(|(r, _)| *r == role) else {
+            continue;
+        };
+        for name in ["port", "redirectPort"] {
+            match counterpart.attribute(&original, name) {
+                Some(value) => connector.set_attribute(&mut doc, name, value),
+                None => {
+                    connector.mut_attributes(&mut doc).remove(name);
+                }
+            }
+        }
+    }
+    write_xml(&doc)
+}
+
+/// Restores (or removes) the `sessionCookieName` attribute of context.xml
+pub fn restore_session_cookie(current: &str, baseline: Option<&str>) -> anyhow::Result<String> {
+    let mut doc = Document::parse_str(current).map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+    let original = baseline
+        .and_then(|b| Document::parse_str(b).ok())
+        .and_then(|d| {
+            d.root_element()
+                .and_then(|r| r.attribute(&d, "sessionCookieName").map(str::to_string))
+        });
+    if let Some(root) = doc.root_element() {
+        match original {
+            Some(value) => root.set_attribute(&mut doc, "sessionCookieName", value),
+            None => {
+                root.mut_attributes(&mut doc).remove("sessionCookieName");
+            }
+        }
+    }
+    write_xml(&doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connector_roles_get_distinct_ports() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Server port="8005"><Service name="Catalina">
+<Connector port="8080" protocol="HTTP/1.1" redirectPort="8443"/>
+<Connector port="8443" protocol="org.apache.coyote.http11.Http11NioProtocol" SSLEnabled="true"/>
+<Connector port="8009" protocol="AJP/1.3" redirectPort="8443"/>
+<Connector port="8081" protocol="HTTP/1.1"/>
+</Service></Server>"#;
+        let mut doc = Document::parse_str(xml).unwrap();
+        let ports = ConnectorPorts {
+            shutdown: 8105,
+            http: 8180,
+            https: 8543,
+            ajp: 8109,
+            additional: 8150,
+        };
+        ports.apply(&mut doc).unwrap();
+
+        let assigned: Vec<(ConnectorRole, Option<&str>, Option<&str>)> = classify_connectors(&doc)
+            .into_iter()
+            .map(|(role, c)| {
+                (
+                    role,
+                    c.attribute(&doc, "port"),
+                    c.attribute(&doc, "redirectPort"),
+                )
+            })
+            .collect();
+        assert_eq!(
+            assigned,
+            vec![
+                (ConnectorRole::Http, Some("8180"), Some("8543")),
+                (ConnectorRole::Https, Some("8543"), None),
+                (ConnectorRole::Ajp, Some("8109"), Some("8543")),
+                (ConnectorRole::Additional(1), Some("8150"), Some("8543")),
+            ]
+        );
+
+        let top = ConnectorPorts {
+            additional: 65535,
+            ..ports
+        };
+        assert!(top.port_for(ConnectorRole::Additional(1)).is_ok());
+        assert!(top.port_for(ConnectorRole::Additional(2)).is_err());
+        let too_many = ConnectorRole::Additional(MAX_ADDITIONAL_CONNECTORS + 1);
+        assert!(ports.port_for(too_many).is_err());
+    }
+
+    #[test]
+    fn test_restore_ports_keeps_ssl_and_user_edits() {
+        let baseline = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Server port="8005"><Service name="Catalina">
+<Connector port="8080" protocol="HTTP/1.1" redirectPort="8443"/>
+<Connector port="8443" protocol="HTTP/1.1" secure="true" scheme="https"/>
+</Service></Server>"#;
+        // The HTTPS connector sits behind a proxy (no SSLEnabled) and comes first
+        let current = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Server port="8105"><Service name="Catalina">
+<Connector port="8543" protocol="HTTP/1.1" secure="true" scheme="https"/>
+<Connector port="8180" protocol="HTTP/1.1" redirectPort="8543" maxThreads="400"/>
+<Connector port="8109" protocol="AJP/1.3"/>
+</Service></Server>"#;
+
+        let restored = restore_ports(current, baseline).unwrap();
+        let doc = Document::parse_str(&restored).unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.attribute(&doc, "port"), Some("8005"));
+
+        let roles = classify_connectors(&doc);
+        let find = |role: ConnectorRole| roles.iter().find(|(r, _)| *r == role).unwrap().1;
+        let http = find(ConnectorRole::Http);
+        assert_eq!(http.attribute(&doc, "port"), Some("8080"));
+        assert_eq!(http.attribute(&doc, "redirectPort"), Some("8443"));
+        assert_eq!(http.attribute(&doc, "maxThreads"), Some("400"));
+        let https = find(ConnectorRole::Https);
+        assert_eq!(https.attribute(&doc, "port"), Some("8443"));
+        assert_eq!(https.attribute(&doc, "redirectPort"), None);
+        // No counterpart in the baseline: left alone
+        assert_eq!(
+            find(ConnectorRole::Ajp).attribute(&doc, "port"),
+            Some("8109")
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::core::config::read_json;
+use crate::core::jdk::Jdk;
+use crate::core::{BundleVersion, DatabaseConfig, DatabaseKind, PropertiesDocument, SetEnv};
+use crate::utils::archive::{create_tar_gz, extract_tar_gz};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest stored at the root of an export archive
+pub const MANIFEST_FILE: &str = "lfr-local-export.json";
+
+/// Directories of Liferay Home that only hold caches or runtime output and are never exported
+const EXCLUDED_HOME_DIRS: &[&str] = &["logs", "osgi/state", ".lfr-local"];
+
+/// The same inside the Tomcat directory
+const EXCLUDED_TOMCAT_DIRS: &[&str] = &["temp", "work", "logs"];
+
+/// Describes an exported bundle so it can be re-created on another machine
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub product: Option<String>,
+    pub version: Option<String>,
+    pub build_number: Option<String>,
+    pub tomcat: String,
+    pub instance_id: u16,
+    pub session_cookie: Option<String>,
+    pub database: Option<String>,
+    pub database_name: Option<String>,
+    pub includes_data: bool,
+    pub exported_at: String,
+}
+
+impl ExportManifest {
+    pub fn new(
+        bundles: &Path,
+        tomcat: &Path,
+        instance_id: u16,
+        includes_data: bool,
+    ) -> Result<Self> {
+        let version = BundleVersion::detect(bundles, tomcat);
+        let props = PropertiesDocument::load(&bundles.join("portal-ext.properties"))?;
+        let database = DatabaseConfig::from_properties(&props);
+
+        Ok(ExportManifest {
+            product: version.product,
+            version: version.version,
+            build_number: version.build_number,
+            tomcat: tomcat
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            instance_id,
+            session_cookie: props.get("session.cookie.name").map(str::to_string),
+            database: database.as_ref().map(|d| d.kind.label().to_string()),
+            database_name: database.map(|d| d.name),
+            includes_data,
+            exported_at: chrono::Local::now().to_rfc3339(),
+        })
+    }
+}
+
+/// Paths, relative to Liferay Home, whose contents are left out of an export
+fn excluded_dirs(tomcat: &str) -> Vec<PathBuf> {
+    let tomcat_dirs = EXCLUDED_TOMCAT_DIRS
+        .iter()
+        .map(|dir| Path::new(tomcat).join(dir));
+    EXCLUDED_HOME_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain(tomcat_dirs)
+        .collect()
+}
+
+/// Packs a bundle without caches, logs or OSGi state, plus a manifest. The bundle
+/// itself is only read.
+pub fn export_bundle(bundles: &Path, out: &Path, manifest: &ExportManifest) -> Result<()> {
+    let excluded = excluded_dirs(&manifest.tomcat);
+    let contents = serde_json::to_string_pretty(manifest)?;
+    create_tar_gz(
+        bundles,
+        out,
+        |relative| {
+            !excluded.iter().any(|dir| relative.starts_with(dir))
+                && (manifest.includes_data || !relative.starts_with("data"))
+        },
+        &[(MANIFEST_FILE, contents.as_bytes())],
+    )
+    .map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Extracts an export archive into `dest` and returns its manifest
+pub fn import_bundle(archive: &Path, dest: &Path) -> Result<ExportManifest> {
+    fs::create_dir_all(dest)?;
+    extract_tar_gz(archive, dest).map_err(|e| anyhow::anyhow!(e))?;
+
+    let manifest_path = dest.join(MANIFEST_FILE);
+    let manifest: ExportManifest =
+        read_json(&manifest_path).map_err(|e| anyhow::anyhow!("Invalid export manifest: {}", e))?;
+    fs::remove_file(manifest_path)?;
+
+    for dir in excluded_dirs(&manifest.tomcat) {
+        fs::create_dir_all(dest.join(dir))?;
+    }
+
+    // Absolute paths from the exporting machine would point nowhere here
+    let prop_path = dest.join("portal-ext.properties");
+    let mut props = PropertiesDocument::load(&prop_path)?;
+    if props.get("liferay.home").is_some() {
+        props.set("liferay.home", &dest.to_string_lossy());
+        props.save(&prop_path)?;
+    }
+    Ok(manifest)
+}
+
+/// Builds the connection for an imported instance, renaming the HSQL files
+/// that came with the archive so they match the new instance's database name
+pub fn rebase_database(
+    bundles: &Path,
+    previous: Option<DatabaseConfig>,
+    name: String,
+) -> Result<DatabaseConfig> {
+    let Some(previous) = previous else {
+        return Ok(DatabaseConfig::new(
+            DatabaseKind::Hsql,
+            name,
+            None,
+            None,
+            None,
+            None,
+        ));
+    };
+
+    if previous.kind == DatabaseKind::Hsql && previous.name != name {
+        let dir = bundles.join("data/hypersonic");
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(suffix) = file_name.strip_prefix(&format!("{}.", previous.name)) {
+                fs::rename(entry.path(), dir.join(format!("{}.{}", name, suffix)))?;
+            }
+        }
+    }
+
+    Ok(DatabaseConfig { name, ..previous })
+}
+
+/// Points the managed setenv.sh block at the importing machine: the debug port follows
+/// the new instance, and a pinned JDK that is not installed here is dropped.
+/// Returns the dropped `JAVA_HOME`.
+pub fn rebase_setenv(setenv: &mut SetEnv, debug_port: u16) -> Option<String> {
+    setenv.set_variable("JPDA_ADDRESS", &format!("localhost:{}", debug_port));
+    let java_home = setenv.variable("JAVA_HOME")?.to_string();
+    if Jdk::from_home(Path::new(&java_home)).is_some() {
+        return None;
+    }
+    setenv.remove_variable("JAVA_HOME");
+    Some(java_home)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_skips_caches_and_round_trips_manifest() {
+        let dir = tempdir().unwrap();
+        let bundles = dir.path().join("bundle");
+        let tomcat = bundles.join("tomcat-9.0.90");
+        for sub in ["conf", "temp", "work", "logs"] {
+            fs::create_dir_all(tomcat.join(sub)).unwrap();
+            fs::write(tomcat.join(sub).join("file"), sub).unwrap();
+        }
+        fs::create_dir_all(bundles.join("osgi/state")).unwrap();
+        fs::create_dir_all(bundles.join("osgi/configs/work")).unwrap();
+        fs::create_dir_all(bundles.join("data/hypersonic")).unwrap();
+        fs::write(bundles.join("osgi/state/cache"), "x").unwrap();
+        fs::write(bundles.join("osgi/configs/work/a.config"), "x").unwrap();
+        fs::write(bundles.join("data/hypersonic/lportal_1.script"), "x").unwrap();
+
+        let manifest = ExportManifest::new(&bundles, &tomcat, 1, false).unwrap();
+        let archive = dir.path().join("bundle.tar.gz");
+        export_bundle(&bundles, &archive, &manifest).unwrap();
+        assert!(!bundles.join(MANIFEST_FILE).exists());
+
+        let dest = dir.path().join("imported");
+        let imported = import_bundle(&archive, &dest).unwrap();
+
+        assert_eq!(imported.instance_id, 1);
+        assert_eq!(imported.tomcat, "tomcat-9.0.90");
+        assert!(dest.join("tomcat-9.0.90/conf/file").exists());
+        assert!(!dest.join("tomcat-9.0.90/work/file").exists());
+        assert!(!dest.join("osgi/state/cache").exists());
+        // Only the cache directories at the top are excluded, not any `work` or `temp`
+        assert!(dest.join("osgi/configs/work/a.config").exists());
+        assert!(dest.join("tomcat-9.0.90/temp").is_dir());
+        assert!(!dest.join("data").exists());
+    }
+
+    #[test]
+    fn test_import_moves_debug_port_and_drops_missing_jdk() {
+        let mut setenv = SetEnv::parse("");
+        setenv.set_variable("JPDA_ADDRESS", "localhost:8200");
+        setenv.set_variable("JAVA_HOME", "/nonexistent/exporter/jdk-17");
+        setenv.set_heap("4g");
+
+        let mut setenv = SetEnv::parse(&setenv.to_string());
+        let dropped = rebase_setenv(&mut setenv, 8500);
+
+        assert_eq!(dropped.as_deref(), Some("/nonexistent/exporter/jdk-17"));
+        assert_eq!(setenv.debug_port(), Some(8500));
+        assert_eq!(setenv.variable("JAVA_HOME"), None);
+        assert_eq!(setenv.options, ["-Xms4g", "-Xmx4g"]);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::core::PropertiesDocument;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Locations of the portal jars relative to the Tomcat directory, newest layout first
+const PORTAL_JARS: &[&str] = &[
+    "webapps/ROOT/WEB-INF/shielded-container-lib/portal-kernel.jar",
+    "webapps/ROOT/WEB-INF/shielded-container-lib/portal-impl.jar",
+    "lib/ext/portal-kernel.jar",
+    "webapps/ROOT/WEB-INF/lib/portal-impl.jar",
+];
+
+/// Locations of release.properties relative to the Tomcat directory
+const RELEASE_PROPERTIES: &[&str] = &[
+    "webapps/ROOT/WEB-INF/classes/release.properties",
+    "webapps/ROOT/release.properties",
+];
+
+/// Exact release information read from the files of an extracted bundle
+#[derive(Debug, Default, PartialEq)]
+pub struct BundleVersion {
+    /// Product name, e.g. "Liferay Digital Experience Platform"
+    pub product: Option<String>,
+    /// Release version, e.g. "2024.q1.1" or "7.4.3.112"
+    pub version: Option<String>,
+    pub build_number: Option<String>,
+    pub tomcat_version: Option<String>,
+    /// Whether the bundle root carries the `.liferay-home` marker
+    pub liferay_home: bool,
+}
+
+impl BundleVersion {
+    /// Inspects the portal jars, release.properties and Tomcat of a bundle
+    pub fn detect(bundles: &Path, tomcat: &Path) -> Self {
+        let mut version = BundleVersion {
+            liferay_home: bundles.join(".liferay-home").exists(),
+            tomcat_version: detect_tomcat_version(tomcat),
+            ..Default::default()
+        };
+
+        for jar in PORTAL_JARS.iter().map(|j| tomcat.join(j)) {
+            if let Some(manifest) = read_manifest(&jar) {
+                version.apply_manifest(&manifest);
+            }
+        }
+
+        for file in RELEASE_PROPERTIES.iter().map(|f| tomcat.join(f)) {
+            if let Ok(content) = fs::read_to_string(&file) {
+                version.apply_release_properties(&content);
+            }
+        }
+
+        version
+    }
+
+    fn apply_manifest(&mut self, manifest: &HashMap<String, String>) {
+        if self.product.is_none() {
+            self.product = manifest
+                .get("Liferay-Portal-Release-Info")
+                .and_then(|info| info.split(" (").next())
+                .map(|name| strip_version_suffix(name.trim()))
+                .or_else(|| manifest.get("Liferay-Portal-Server-Info").cloned());
+        }
+        if self.version.is_none() {
+            self.version = manifest.get("Liferay-Portal-Version").cloned();
+        }
+        if self.build_number.is_none() {
+            self.build_number = manifest.get("Liferay-Portal-Build-Number").cloned();
+        }
+    }
+
+    fn apply_release_properties(&mut self, content: &str) {
+        let doc = PropertiesDocument::parse(content);
+        let first = |keys: &[&str]| keys.iter().find_map(|k| doc.get(k)).map(str::to_string);
+
+        if self.product.is_none() {
+            self.product = first(&["release.info.product", "liferay.product.name"]);
+        }
+        if self.version.is_none() {
+            self.version = first(&["release.info.version", "liferay.product.version"]);
+        }
+        if self.build_number.is_none() {
+            self.build_number = first(&["release.info.build.number", "liferay.build.number"]);
+        }
+    }
+
+    /// Quarterly releases are named e.g. 2024.q1.1
+    pub fn is_quarterly(&self) -> bool {
+        self.version
+            .as_deref()
+            .map(|v| v.to_lowercase().contains(".q"))
+            .unwrap_or(false)
+    }
+
+    /// Q1 quarterly releases are the long-term-support releases
+    pub fn is_lts(&self) -> bool {
+        self.version
+            .as_deref()
+            .map(|v| {
+                let v = v.to_lowercase();
+                v.contains(".q1.") || v.ends_with("-lts")
+            })
+            .unwrap_or(false)
+    }
+
+    /// Human-readable release channel for `summary`
+    pub fn release_type(&self) -> &'static str {
+        if self.is_lts() {
+            "Quarterly (LTS)"
+        } else if self.is_quarterly() {
+            "Quarterly"
+        } else if self.version.is_some() {
+            "GA / Update"
+        } else {
+            "Unknown"
+        }
+    }
+
+    /// Java major versions the release is supported on, if the version is known
+    pub fn supported_java(&self) -> Option<&'static [u16]> {
+        let version = self.version.as_deref()?.to_lowercase();
+        let mut parts = version.split(['.', '-']);
+        let major: u16 = parts.next()?.parse().ok()?;
+        let minor = parts.next()?;
+
+        if self.is_quarterly() {
+            return Some(if major >= 2024 { &[17, 21] } else { &[11, 17] });
+        }
+        match (major, minor.parse::<u16>().ok()?) {
+            (7, 4) => Some(&[8, 11, 17]),
+            (7, 2..=3) => Some(&[8, 11]),
+            (7, 0..=1) | (6, _) => Some(&[8]),
+            _ => None,
+        }
+    }
+
+    /// Explains why `java_major` does not suit this release, if it does not
+    pub fn java_warning(&self, java_major: u16) -> Option<String> {
+        let supported = self.supported_java()?;
+        if supported.contains(&java_major) {
+            return None;
+        }
+        let list: Vec<String> = supported.iter().map(u16::to_string).collect();
+        Some(format!(
+            "Java {} is not supported by Liferay {} (supported: {})",
+            java_major,
+            self.version.as_deref().unwrap_or_default(),
+            list.join(", ")
+        ))
+    }
+}
+
+/// Removes a trailing version number from a release-info product name
+fn strip_version_suffix(name: &str) -> String {
+    match name.rsplit_once(' ') {
+        Some((product, last)) if last.chars().next().is_some_and(|c| c.is_ascii_digit()) => {
+            product.to_string()
+        }
+        _ => name.to_string(),
+    }
+}
+
+/// Reads META-INF/MANIFEST.MF from a jar, joining continuation lines
+fn read_manifest(jar: &Path) -> Option<HashMap<String, String>> {
+    let file = File::open(jar).ok()?;
+    let mut archive = zip::ZipArchive::new(file).ok()?;
+    let mut entry = archive.by_name("META-INF/MANIFEST.MF").ok()?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content).ok()?;
+    Some(parse_manifest(&content))
+}
+
+fn parse_manifest(content: &str) -> HashMap<String, String> {
+    let mut attributes: HashMap<String, String> = HashMap::new();
+    let mut last_key: Option<String> = None;
+
+    for line in content.lines() {
+        if let Some(continuation) = line.strip_prefix(' ') {
+            if let Some(value) = last_key.as_ref().and_then(|k| attributes.get_mut(k)) {
+                value.push_str(continuation);
+            }
+        } else if let Some((key, value)) = line.split_once(": ") {
+            attributes.insert(key.to_string(), value.to_string());
+            last_key = Some(key.to_string());
+        }
+    }
+    attributes
+}
+
+/// Reads the Tomcat version from catalina.jar's ServerInfo.properties, falling
+/// back to the version in the directory name
+fn detect_tomcat_version(tomcat: &Path) -> Option<String> {
+    let from_jar = File::open(tomcat.join("lib/catalina.jar"))
+        .ok()
+        .and_then(|file| zip::ZipArchive::new(file).ok())
+        .and_then(|mut archive| {
+            let mut entry = archive
+                .by_name("org/apache/catalina/util/ServerInfo.properties")
+                .ok()?;
+            let mut content = String::new();
+            entry.read_to_string(&mut content).ok()?;
+            content.lines().find_map(|line| {
+                line.strip_prefix("server.info=")
+                    .and_then(|info| info.strip_prefix("Apache Tomcat/"))
+                    .map(|v| v.trim().to_string())
+            })
+        });
+
+    from_jar.or_else(|| {
+        tomcat
+            .file_name()?
+            .to_str()?
+            .strip_prefix("tomcat-")
+            .map(|v| v.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_portal_manifest() {
+        let manifest = parse_manifest(
+            "Manifest-Version: 1.0\r\n\
+             Liferay-Portal-Release-Info: Liferay Digital Experience Platform 2024.q1.1 (Build 7410 / \r\n \
+             January 31, 2024)\r\n\
+             Liferay-Portal-Version: 2024.q1.1\r\n\
+             Liferay-Portal-Build-Number: 7410\r\n",
+        );
+        let mut version = BundleVersion::default();
+        version.apply_manifest(&manifest);
+
+        assert_eq!(
+            version.product.as_deref(),
+            Some("Liferay Digital Experience Platform")
+        );
+        assert_eq!(version.version.as_deref(), Some("2024.q1.1"));
+        assert_eq!(version.build_number.as_deref(), Some("7410"));
+        assert!(version.is_lts());
+        assert_eq!(version.release_type(), "Quarterly (LTS)");
+        assert_eq!(version.supported_java(), Some(&[17, 21][..]));
+
+        version.version = Some("7.3.10".to_string());
+        assert_eq!(version.supported_java(), Some(&[8, 11][..]));
+    }
+}
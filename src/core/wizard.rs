@@ -0,0 +1,91 @@
+use crate::core::PropertiesDocument;
+use anyhow::Result;
+
+/// Written by the setup wizard in Liferay Home and loaded after portal-ext.properties
+pub const SETUP_WIZARD_FILE: &str = "portal-setup-wizard.properties";
+
+/// portal-ext.properties keys written by `configure --no-wizard`
+pub const WIZARD_KEYS: &[&str] = &[
+    "setup.wizard.enabled",
+    "company.default.name",
+    "company.default.web.id",
+    "company.default.locale",
+    "company.default.time.zone",
+    "default.admin.screen.name",
+    "default.admin.email.address.prefix",
+    "default.admin.password",
+];
+
+/// What the setup wizard would otherwise ask for; unset values keep Liferay's defaults
+#[derive(Debug, Default)]
+pub struct WizardSettings {
+    pub company_name: Option<String>,
+    pub admin_screen_name: Option<String>,
+    pub admin_email: Option<String>,
+    pub admin_password: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+}
+
+impl WizardSettings {
+    /// Disables the wizard and writes every value that was given. Liferay builds the
+    /// admin email from a prefix and the company web ID, so the email is split at `@`.
+    pub fn apply(&self, props: &mut PropertiesDocument) -> Result<()> {
+        props.set("setup.wizard.enabled", "false");
+        if let Some(name) = &self.company_name {
+            props.set("company.default.name", name);
+        }
+        if let Some(email) = &self.admin_email {
+            let (prefix, domain) = email
+                .split_once('@')
+                .filter(|(p, d)| !p.is_empty() && d.contains('.'))
+                .ok_or_else(|| anyhow::anyhow!("'{}' is not a valid email address", email))?;
+            props.set("default.admin.email.address.prefix", prefix);
+            props.set("company.default.web.id", domain);
+        }
+        if let Some(locale) = &self.locale {
+            props.set("company.default.locale", &locale.replace('-', "_"));
+        }
+        if let Some(timezone) = &self.timezone {
+            props.set("company.default.time.zone", timezone);
+        }
+        if let Some(screen_name) = &self.admin_screen_name {
+            props.set("default.admin.screen.name", screen_name);
+        }
+        if let Some(password) = &self.admin_password {
+            props.set("default.admin.password", password);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_settings() {
+        let settings = WizardSettings {
+            company_name: Some("Acme".to_string()),
+            admin_email: Some("admin@acme.com".to_string()),
+            locale: Some("en-GB".to_string()),
+            ..WizardSettings::default()
+        };
+        let mut props = PropertiesDocument::parse("session.cookie.name=LFR_SESSION_2\n");
+        settings.apply(&mut props).unwrap();
+        assert_eq!(props.get("setup.wizard.enabled"), Some("false"));
+        assert_eq!(
+            props.get("default.admin.email.address.prefix"),
+            Some("admin")
+        );
+        assert_eq!(props.get("company.default.web.id"), Some("acme.com"));
+        assert_eq!(props.get("company.default.locale"), Some("en_GB"));
+        assert_eq!(props.get("default.admin.password"), None);
+
+        let invalid = WizardSettings {
+            admin_email: Some("admin".to_string()),
+            ..WizardSettings::default()
+        };
+        assert!(invalid.apply(&mut props).is_err());
+    }
+}
@@ -2,41 +2,550 @@ mod cli;
 mod core;
 mod utils;
 
-use crate::cli::{App, AppCommands};
-use crate::core::{BundleResolver, LiferayProject, Workspace};
+use crate::cli::{App, AppCommands, DbAction, InstanceSelector, JvmCommands, SnapshotCommands};
+use crate::core::config::LocalConfig;
+use crate::core::database::JDBC_KEYS;
+use crate::core::doctor::{self, CheckStatus};
+use crate::core::host::{apply_host, instance_host, instance_url, validate_host, HOST_KEYS};
+use crate::core::https::{disable_connector, enable_connector, HttpsRecord, HttpsStore};
+use crate::core::ide::IdeInstance;
+use crate::core::jdk::{self, Jdk};
+use crate::core::overrides::{find_conflicts, fix_conflicts, load_layers, ConflictFix};
+use crate::core::ports::{
+    clear_elasticsearch_port, elasticsearch_port, gogo_port, set_elasticsearch_port, set_gogo_port,
+    InstancePorts, ELASTICSEARCH_CONFIG, GOGO_KEY,
+};
+use crate::core::tomcat::{
+    additional_connectors, classify_connectors, restore_ports, restore_session_cookie, write_xml,
+    ConnectorRole,
+};
+use crate::core::transfer::{
+    export_bundle, import_bundle, rebase_database, rebase_setenv, ExportManifest,
+};
+use crate::core::wizard::{WizardSettings, WIZARD_KEYS};
+use crate::core::{
+    BackupStore, Baseline, BundleResolver, BundleVersion, DatabaseConfig, DatabaseKind,
+    LiferayProject, PortPlan, PropertiesDocument, SetEnv, SnapshotStore, TemplateCache, Workspace,
+};
 use crate::utils::archive::extract_bundle;
 use crate::utils::download::download_file;
+use crate::utils::net::{has_established_connection, is_listening, is_port_open};
+use crate::utils::ChangeSet;
+use chrono::Local;
 use clap::Parser;
-use dialoguer::Confirm;
-use edit_xml::{Document, Element};
+use dialoguer::{Confirm, Select};
+use edit_xml::Document;
 use std::fs;
-use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::Duration;
+use std::time::Instant;
 use sysinfo::System;
 
-/// Helper to find all Connector elements at any depth in the XML tree
-fn collect_connectors(doc: &Document) -> Vec<Element> {
-    let mut connectors = Vec::new();
-    let mut stack: Vec<Element> = doc.root_element().into_iter().collect();
+/// Downloads (or reuses a cached copy of) a bundle and materialises it in `target_dir`.
+/// Returns `false` if the user declined to overwrite an existing directory.
+fn install_bundle(
+    current_dir: &Path,
+    product: Option<String>,
+    url: Option<String>,
+    base_url: Option<String>,
+    target_dir: &Path,
+) -> anyhow::Result<bool> {
+    let name = target_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "bundle".to_string());
 
-    while let Some(el) = stack.pop() {
-        if el.name(doc) == "Connector" {
-            connectors.push(el);
+    let (download_url, cached_path, template_key) = match url {
+        Some(u) => {
+            let key = match BundleResolver::release_from_url(&u) {
+                Some((product_type, version)) => {
+                    TemplateCache::key_for_release(product_type, &version)
+                }
+                None => TemplateCache::key_for_archive(u.rsplit('/').next().unwrap_or(&u)),
+            };
+            (Some(u), None, key)
+        }
+        None => match product {
+            Some(p) => {
+                let (prefix, product_type, default_base) = if p.starts_with("portal-") {
+                    (
+                        p.strip_prefix("portal-").unwrap(),
+                        "portal",
+                        crate::core::resolver::DEFAULT_PORTAL_BASE_URL,
+                    )
+                } else if p.starts_with("dxp-") {
+                    (
+                        p.strip_prefix("dxp-").unwrap(),
+                        "dxp",
+                        crate::core::resolver::DEFAULT_DXP_BASE_URL,
+                    )
+                } else {
+                    anyhow::bail!("Unknown product prefix: {}", p);
+                };
+
+                let base = base_url.clone().unwrap_or_else(|| default_base.to_string());
+                let resolved_version = BundleResolver::find_latest_in_cdn(&base, prefix)?;
+                let key = TemplateCache::key_for_release(product_type, &resolved_version);
+
+                // Check the extracted template cache, then the archive cache
+                if TemplateCache::find(&key).is_some() {
+                    (None, None, key)
+                } else if let Some(path) =
+                    BundleResolver::find_in_cache(product_type, &resolved_version)
+                {
+                    println!("Found cached bundle: {}", path.display());
+                    (None, Some(path), key)
+                } else {
+                    // If not in cache, resolve full URL
+                    let resolved_url = BundleResolver::resolve(&p, base_url)?;
+                    (Some(resolved_url), None, key)
+                }
+            }
+            None => anyhow::bail!("You must provide either a --product or a --url"),
+        },
+    };
+
+    if target_dir.exists() {
+        if !Confirm::new()
+            .with_prompt(format!(
+                "Directory '{}' already exists. Overwrite?",
+                target_dir.display()
+            ))
+            .interact()
+            .unwrap_or(false)
+        {
+            return Ok(false);
+        }
+        fs::remove_dir_all(target_dir)?;
+    }
+    fs::create_dir_all(target_dir)?;
+
+    let template = match TemplateCache::find(&template_key) {
+        Some(path) => {
+            println!("Found cached template: {}", path.display());
+            Some(path)
         }
-        for child in el.children(doc) {
-            if let Some(child_el) = child.as_element() {
-                stack.push(child_el);
+        None => {
+            let archive_to_extract = if let Some(url) = download_url {
+                let tmp_zip = current_dir.join(format!("{}.zip", name));
+                println!("Downloading bundle from {}...", url);
+                download_file(&url, &tmp_zip)?;
+                tmp_zip
+            } else {
+                cached_path.unwrap()
+            };
+
+            let template = if TemplateCache::root().is_some() {
+                println!("Extracting bundle into template cache...");
+                Some(TemplateCache::populate(&template_key, &archive_to_extract)?)
+            } else {
+                println!("Extracting bundle to {}...", target_dir.display());
+                extract_bundle(&archive_to_extract, target_dir, true)
+                    .map_err(|e| anyhow::anyhow!("Extraction failed: {}", e))?;
+                None
+            };
+
+            // Only remove if it was a download (it's in current_dir)
+            if archive_to_extract.parent() == Some(current_dir) {
+                let _ = fs::remove_file(&archive_to_extract);
             }
+            template
+        }
+    };
+
+    if let Some(template) = template {
+        println!("Materialising bundle in {}...", target_dir.display());
+        let started = Instant::now();
+        let stats = TemplateCache::materialise(&template, target_dir)?;
+        println!(
+            "{} reflinked, {} hardlinked, {} copied in {:.1}s",
+            stats.reflinked,
+            stats.hardlinked,
+            stats.copied,
+            started.elapsed().as_secs_f64()
+        );
+    }
+
+    Ok(true)
+}
+
+/// Copies `configs/common` and `configs/<env>` over the bundle, like Gradle `initBundle`
+fn overlay_configs(configs_dir: &Path, home: &Path, environment: &str) -> anyhow::Result<()> {
+    let options = fs_extra::dir::CopyOptions {
+        overwrite: true,
+        content_only: true,
+        ..Default::default()
+    };
+    for layer in ["common", environment] {
+        let src = configs_dir.join(layer);
+        if src.is_dir() {
+            println!("Applying configs/{}...", layer);
+            fs_extra::dir::copy(&src, home, &options)
+                .map_err(|e| anyhow::anyhow!("Failed to copy configs/{}: {}", layer, e))?;
         }
     }
-    connectors
+    Ok(())
+}
+
+/// Offsets ports (server.xml, Gogo shell, Elasticsearch sidecar and JMX), sets the session
+/// cookie and writes the JDBC connection for an instance
+fn configure_instance(
+    changes: &mut ChangeSet,
+    tomcat: &Path,
+    bundles: &Path,
+    instance_id: u16,
+    ports: &InstancePorts,
+    database: &DatabaseConfig,
+) -> anyhow::Result<()> {
+    let cookie = format!("LFR_SESSION_{}", instance_id);
+
+    let server_xml_path = tomcat.join("conf/server.xml");
+    let server_xml_raw = changes.read(&server_xml_path)?;
+    let mut server_doc =
+        Document::parse_str(&server_xml_raw).map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+    ports.connectors().apply(&mut server_doc)?;
+    changes.write(&server_xml_path, write_xml(&server_doc)?);
+
+    let context_xml_path = tomcat.join("conf/context.xml");
+    let context_xml_raw = changes.read(&context_xml_path)?;
+    let mut context_doc =
+        Document::parse_str(&context_xml_raw).map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+    if let Some(root) = context_doc.root_element() {
+        root.set_attribute(&mut context_doc, "sessionCookieName", cookie.clone());
+    }
+    changes.write(&context_xml_path, write_xml(&context_doc)?);
+
+    let prop_path = bundles.join("portal-ext.properties");
+    let mut props = PropertiesDocument::parse(&changes.read_or_default(&prop_path)?);
+    props.set("session.cookie.name", &cookie);
+    set_gogo_port(&mut props, ports.gogo);
+    database.apply(&mut props);
+    changes.write(&prop_path, props.to_string());
+
+    let es_config_path = bundles.join(ELASTICSEARCH_CONFIG);
+    let mut es_config = PropertiesDocument::parse(&changes.read_or_default(&es_config_path)?);
+    set_elasticsearch_port(&mut es_config, ports.elasticsearch);
+    changes.write(&es_config_path, es_config.to_string());
+
+    let setenv_path = tomcat.join("bin/setenv.sh");
+    let mut setenv = SetEnv::parse(&changes.read_or_default(&setenv_path)?);
+    setenv.set_jmx_port(ports.jmx);
+    changes.write(&setenv_path, setenv.to_string());
+
+    if database.find_driver_jar(tomcat).is_none() {
+        println!(
+            "WARNING: No {} JDBC driver jar found under {}. Copy it into {} before starting.",
+            database.kind.label(),
+            tomcat.display(),
+            tomcat.join("lib/ext").display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Adds the HTTPS connector and records it, generating the keystore unless it already
+/// covers the same host names. A new keystore is staged and swapped in by `changes`.
+fn enable_https(
+    changes: &mut ChangeSet,
+    tomcat: &Path,
+    bundles: &Path,
+    ports: &InstancePorts,
+    java_home: Option<PathBuf>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let store = HttpsStore::new(bundles, tomcat);
+    let props = PropertiesDocument::parse(
+        &changes.read_or_default(&bundles.join("portal-ext.properties"))?,
+    );
+    let mut hosts = vec!["localhost".to_string()];
+    let host = instance_host(&props);
+    if host != "localhost" {
+        hosts.push(host.to_string());
+    }
+
+    let server_xml_path = tomcat.join("conf/server.xml");
+    let mut doc = Document::parse_str(&changes.read(&server_xml_path)?)
+        .map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+    let added = enable_connector(&mut doc, ports.ssl, &HttpsStore::keystore_reference())?;
+    changes.write(&server_xml_path, write_xml(&doc)?);
+
+    let record = HttpsRecord {
+        keystore: store.keystore(),
+        hosts: hosts.clone(),
+        connector_added: added || store.load().is_some_and(|r| r.connector_added),
+    };
+    changes.write(
+        &store.record_path(),
+        format!("{}\n", serde_json::to_string_pretty(&record)?),
+    );
+
+    if store.is_current(&hosts) {
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "Would generate a self-signed certificate for {} in {}",
+            hosts.join(", "),
+            store.keystore().display()
+        );
+        return Ok(());
+    }
+    let keytool = jdk::active(java_home.as_deref())
+        .map(|(jdk, _)| jdk.home.join("bin/keytool"))
+        .unwrap_or_else(|| PathBuf::from("keytool"));
+    store.generate(&keytool, &hosts)?;
+    changes.replace(&store.keystore(), &store.staged_keystore());
+    println!(
+        "Generated a self-signed certificate for {} in {}",
+        hosts.join(", "),
+        store.keystore().display()
+    );
+    Ok(())
+}
+
+/// Reports properties that a file loaded after portal-ext.properties overrides, then
+/// stages `fix` (or the one the user picks when `prompt` is set). Returns the number of conflicts.
+fn reconcile_properties(
+    changes: &mut ChangeSet,
+    bundles: &Path,
+    fix: Option<ConflictFix>,
+    prompt: bool,
+) -> anyhow::Result<usize> {
+    let portal_ext = bundles.join("portal-ext.properties");
+    let mut layers = load_layers(bundles, |path| changes.read(path).ok());
+    let conflicts = find_conflicts(&layers, &portal_ext);
+    if conflicts.is_empty() {
+        return Ok(0);
+    }
+
+    println!(
+        "WARNING: {} portal propert{} set differently in several files; the last file wins:",
+        conflicts.len(),
+        if conflicts.len() == 1 {
+            "y is"
+        } else {
+            "ies are"
+        }
+    );
+    for conflict in &conflicts {
+        println!("  {}", conflict.key);
+        for (i, (path, value)) in conflict.values.iter().enumerate() {
+            let value = if conflict.key.contains("password") {
+                "****"
+            } else {
+                value
+            };
+            let wins = if i + 1 == conflict.values.len() {
+                " (wins)"
+            } else {
+                ""
+            };
+            println!(
+                "    {:<40} {}{}",
+                path.strip_prefix(bundles).unwrap_or(path).to_string_lossy(),
+                value,
+                wins
+            );
+        }
+    }
+
+    let fix = match fix {
+        Some(fix) => Some(fix),
+        None if prompt => {
+            let choices = [
+                "Remove the later definitions (keep the portal-ext.properties value)",
+                "Move the winning values into portal-ext.properties",
+                "Leave them as they are",
+            ];
+            match Select::new()
+                .with_prompt("Resolve the conflicts?")
+                .items(&choices)
+                .default(0)
+                .interact()
+                .unwrap_or(2)
+            {
+                0 => Some(ConflictFix::Remove),
+                1 => Some(ConflictFix::Move),
+                _ => None,
+            }
+        }
+        None => None,
+    };
+    if let Some(fix) = fix {
+        fix_conflicts(&mut layers, &conflicts, fix, &portal_ext);
+        for layer in layers {
+            let updated = layer.props.to_string();
+            if changes.read(&layer.path).unwrap_or_default() != updated {
+                changes.write(&layer.path, updated);
+            }
+        }
+    }
+    Ok(conflicts.len())
+}
+
+/// Ports the bundle listens on as instance `id` (if configured), which it may keep
+/// using while it is being reconfigured
+fn own_ports(plan: &PortPlan, id: Option<u16>, additional: u16) -> Vec<u16> {
+    id.and_then(|id| plan.all_ports(id, additional).ok())
+        .map(|ports| ports.iter().map(|(_, port)| *port).collect())
+        .unwrap_or_default()
+}
+
+/// Plans the ports of instance `id` and of the `tomcat`'s additional connectors,
+/// failing if any of them is reserved or in use by anything but the bundle's `own` ports
+fn plan_ports(
+    plan: &PortPlan,
+    id: u16,
+    tomcat: &Path,
+    own: &[u16],
+) -> anyhow::Result<InstancePorts> {
+    let additional = additional_connectors(tomcat);
+    let conflicts = plan.conflicts(id, additional, |port| {
+        !own.contains(&port) && is_port_open(port)
+    })?;
+    if !conflicts.is_empty() {
+        let list: Vec<String> = conflicts
+            .iter()
+            .map(|(name, port)| format!("{} ({})", port, name))
+            .collect();
+        anyhow::bail!(
+            "Instance {} cannot use port(s) {}: reserved in the port plan or already in use.",
+            id,
+            list.join(", ")
+        );
+    }
+    plan.ports_for(id)
+}
+
+/// Finds the JDK for `configure --java`: a JDK home directory or an installed major version
+fn select_jdk(java: &str) -> anyhow::Result<Jdk> {
+    let path = Path::new(java);
+    if path.is_dir() {
+        return Jdk::from_home(path)
+            .ok_or_else(|| anyhow::anyhow!("{} has no JDK release file.", path.display()));
+    }
+    let major: u16 = java
+        .parse()
+        .map_err(|_| anyhow::anyhow!("'{}' is neither a Java version nor a directory.", java))?;
+    let installed = jdk::discover();
+    installed
+        .iter()
+        .find(|jdk| jdk.major() == major)
+        .cloned()
+        .ok_or_else(|| {
+            let found: Vec<String> = installed.iter().map(|j| j.major().to_string()).collect();
+            anyhow::anyhow!(
+                "No Java {} JDK found (installed: {}).",
+                major,
+                if found.is_empty() {
+                    "none".to_string()
+                } else {
+                    found.join(", ")
+                }
+            )
+        })
+}
+
+/// Describes a JDWP port without connecting to it (a probe would disturb the agent)
+fn debug_state(port: u16) -> String {
+    let state = if has_established_connection(port) {
+        "debugger attached"
+    } else if is_listening(port) {
+        "listening"
+    } else {
+        "not listening"
+    };
+    format!("{} ({})", port, state)
+}
+
+/// Backs up the files a command is about to change, then applies (or previews) the changes
+fn commit_changes(
+    changes: &ChangeSet,
+    home: &Path,
+    operation: &str,
+    dry_run: bool,
+    show_diff: bool,
+) -> anyhow::Result<bool> {
+    if !dry_run {
+        BackupStore::new(home).record(
+            operation,
+            &changes.originals(),
+            &changes.replaced(),
+            None,
+            LocalConfig::load()?.max_backups,
+        )?;
+    }
+    changes.finish(dry_run, show_diff)
+}
+
+/// Saves the instance state as a `pre-restore-<timestamp>` snapshot and records it
+/// for undo, so the backup does not have to copy the data directory
+fn save_pre_restore(
+    store: &SnapshotStore,
+    changes: &ChangeSet,
+    home: &Path,
+    instance_id: u16,
+    name: &str,
+) -> anyhow::Result<()> {
+    let before = format!("pre-restore-{}", Local::now().format("%Y%m%d-%H%M%S"));
+    store.save(&before)?;
+    println!("Saved the current state as snapshot '{}'.", before);
+    BackupStore::new(home).record(
+        &format!("snapshot {} restore {}", instance_id, name),
+        &changes.originals(),
+        &changes.replaced(),
+        Some(&before),
+        LocalConfig::load()?.max_backups,
+    )
+}
+
+/// Stock Tomcat ports, used by `reset --ports` on bundles without a baseline
+fn default_ports(server_xml: &str) -> anyhow::Result<String> {
+    let mut doc =
+        Document::parse_str(server_xml).map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+    // The default plan's instance 0 uses the stock ports
+    PortPlan::default()
+        .ports_for(0)?
+        .connectors()
+        .apply(&mut doc)?;
+    write_xml(&doc)
+}
+
+/// Records the pristine baseline of a freshly installed bundle
+fn capture_baseline(home: &Path) -> anyhow::Result<()> {
+    let project = LiferayProject {
+        current_dir: home.to_path_buf(),
+        tomcat: None,
+        prompt_tomcat: false,
+    };
+    let tomcat = project.find_tomcat(home)?;
+    Baseline::new(home).capture(&tomcat)?;
+    Ok(())
+}
+
+/// Refuses `--dry-run` for commands that would otherwise do their work for real
+fn check_dry_run(args: &App) -> anyhow::Result<()> {
+    if let Some(command) = args
+        .dry_run
+        .then(|| args.command.without_dry_run())
+        .flatten()
+    {
+        anyhow::bail!(
+            "'{}' cannot be previewed; run it without --dry-run",
+            command
+        );
+    }
+    Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
     let args = App::parse();
+    check_dry_run(&args)?;
     let ws = LiferayProject {
         current_dir: std::env::current_dir().unwrap_or_default(),
+        tomcat: args.tomcat.clone(),
+        prompt_tomcat: args.command.writes_files(),
     };
 
     match args.command {
@@ -45,76 +554,46 @@ fn main() -> anyhow::Result<()> {
             url,
             base_url,
             name,
+            from_workspace,
+            environment,
         } => {
-            let (download_url, cached_path) = match url {
-                Some(u) => (Some(u), None),
-                None => match product {
-                    Some(p) => {
-                        let (prefix, product_type, default_base) = if p.starts_with("portal-") {
-                            (
-                                p.strip_prefix("portal-").unwrap(),
-                                "portal",
-                                crate::core::resolver::DEFAULT_PORTAL_BASE_URL,
-                            )
-                        } else if p.starts_with("dxp-") {
-                            (
-                                p.strip_prefix("dxp-").unwrap(),
-                                "dxp",
-                                crate::core::resolver::DEFAULT_DXP_BASE_URL,
-                            )
-                        } else {
-                            anyhow::bail!("Unknown product prefix: {}", p);
-                        };
-
-                        let base = base_url.clone().unwrap_or_else(|| default_base.to_string());
-                        let resolved_version = BundleResolver::find_latest_in_cdn(&base, prefix)?;
-
-                        // Check cache first
-                        if let Some(path) =
-                            BundleResolver::find_in_cache(product_type, &resolved_version)
-                        {
-                            println!("Found cached bundle: {}", path.display());
-                            (None, Some(path))
-                        } else {
-                            // If not in cache, resolve full URL
-                            let resolved_url = BundleResolver::resolve(&p, base_url)?;
-                            (Some(resolved_url), None)
-                        }
-                    }
-                    None => anyhow::bail!("You must provide either a --product or a --url"),
-                },
-            };
+            if from_workspace {
+                let root = ws.find_root()?;
+                let product = ws
+                    .get_workspace_property(&root, "liferay.workspace.product")
+                    .map(|p| BundleResolver::normalize_workspace_product(&p));
+                let url = ws.get_workspace_property(&root, "liferay.workspace.bundle.url");
+                if product.is_none() && url.is_none() {
+                    anyhow::bail!(
+                        "Neither liferay.workspace.product nor liferay.workspace.bundle.url is set in {}",
+                        root.join("gradle.properties").display()
+                    );
+                }
+                let home = ws.get_workspace_home(&root);
 
-            let target_dir = ws.current_dir.join(&name);
-            if target_dir.exists() {
-                if !Confirm::new()
-                    .with_prompt(format!("Directory '{}' already exists. Overwrite?", name))
-                    .interact()
-                    .unwrap_or(false)
-                {
+                if !install_bundle(&ws.current_dir, product, url, base_url, &home)? {
                     return Ok(());
                 }
-                fs::remove_dir_all(&target_dir)?;
-            }
-            fs::create_dir_all(&target_dir)?;
 
-            let archive_to_extract = if let Some(url) = download_url {
-                let tmp_zip = ws.current_dir.join(format!("{}.zip", name));
-                println!("Downloading bundle from {}...", url);
-                download_file(&url, &tmp_zip)?;
-                tmp_zip
-            } else {
-                cached_path.unwrap()
-            };
+                let environment = environment
+                    .or_else(|| ws.get_workspace_property(&root, "liferay.workspace.environment"))
+                    .unwrap_or_else(|| "local".to_string());
+                overlay_configs(&root.join("configs"), &home, &environment)?;
+                capture_baseline(&home)?;
 
-            println!("Extracting bundle to {}...", target_dir.display());
-            extract_bundle(&archive_to_extract, &target_dir, true)
-                .map_err(|e| anyhow::anyhow!("Extraction failed: {}", e))?;
+                println!(
+                    "Success! Workspace bundle initialized in '{}'.",
+                    home.display()
+                );
+                return Ok(());
+            }
 
-            // Only remove if it was a download (it's in current_dir)
-            if archive_to_extract.parent() == Some(&ws.current_dir) {
-                let _ = fs::remove_file(&archive_to_extract);
+            let name = name.unwrap_or_default();
+            let target_dir = ws.current_dir.join(&name);
+            if !install_bundle(&ws.current_dir, product, url, base_url, &target_dir)? {
+                return Ok(());
             }
+            capture_baseline(&target_dir)?;
 
             println!("Success! Bundle initialized in '{}'.", name);
             Ok(())
@@ -122,93 +601,229 @@ fn main() -> anyhow::Result<()> {
 
         AppCommands::Configure {
             instance_id,
+            next_free,
             workspace_path,
+            db,
             db_name,
+            db_host,
+            db_port,
+            db_user,
+            db_password,
             clear_data,
+            heap,
+            jvm_opts,
+            java,
+            https,
+            host,
+            no_wizard,
+            company_name,
+            admin_screen_name,
+            admin_email,
+            admin_password,
+            locale,
+            timezone,
         } => {
             let root_path = workspace_path.unwrap_or(ws.find_root()?);
             let tomcat = ws.find_tomcat(&root_path)?;
             let bundles = ws.get_bundles_dir(&root_path);
 
-            let offset = instance_id * 100;
-            let p_stop = 8005 + offset;
-            let p_http = 8080 + offset;
-            let p_ajp = 8009 + offset;
-            let p_ssl = 8443 + offset;
-            let cookie = format!("LFR_SESSION_{}", instance_id);
-            let db = db_name.unwrap_or(format!("lportal_{}", instance_id));
+            let plan = LocalConfig::load()?.ports;
+            let current_id = ws.get_instance_id(&root_path);
+            let additional = additional_connectors(&tomcat);
+            let own = own_ports(&plan, current_id, additional);
+            // An already configured bundle keeps its ID under `auto` while its ports are free
+            let kept =
+                current_id.and_then(|id| Some((id, plan_ports(&plan, id, &tomcat, &own).ok()?)));
+            let (instance_id, ports) = match (instance_id, kept) {
+                (Some(InstanceSelector::Id(id)), _) if !next_free => {
+                    (id, plan_ports(&plan, id, &tomcat, &own)?)
+                }
+                (_, Some((id, ports))) => {
+                    println!("Keeping instance ID {}.", id);
+                    (id, ports)
+                }
+                _ => {
+                    let in_use = |port| !own.contains(&port) && is_port_open(port);
+                    let (id, ports) =
+                        plan.next_free(&ws.known_instance_ids(), additional, in_use)?;
+                    let list: Vec<String> = ports
+                        .all()
+                        .iter()
+                        .map(|(name, port)| format!("{} {}", name, port))
+                        .collect();
+                    println!("Next free instance ID: {} ({})", id, list.join(", "));
+                    (id, ports)
+                }
+            };
+
+            if let Some(host) = &host {
+                validate_host(host)?;
+            }
+
+            let existing = fs::read_to_string(bundles.join("portal-ext.properties"))
+                .ok()
+                .and_then(|raw| DatabaseConfig::from_properties(&PropertiesDocument::parse(&raw)));
+            let mut database = DatabaseConfig::for_instance(existing, db, current_id, instance_id);
+            if let Some(name) = db_name {
+                database.name = name;
+            }
+            if let Some(host) = db_host {
+                database.host = host;
+            }
+            if let Some(port) = db_port {
+                database.port = port;
+            }
+            if let Some(user) = db_user {
+                database.user = user;
+            }
+            if let Some(password) = db_password {
+                database.password = password;
+            }
+
+            // Bundles not created by `init` get their baseline before the first change
+            if !args.dry_run && Baseline::new(&bundles).capture(&tomcat)? {
+                println!("Recorded baseline configuration for reset.");
+            }
 
             println!("--- Reconfiguring: Instance {} ---", instance_id);
+            let mut changes = ChangeSet::new();
+            configure_instance(
+                &mut changes,
+                &tomcat,
+                &bundles,
+                instance_id,
+                &ports,
+                &database,
+            )?;
 
-            let server_xml_path = tomcat.join("conf/server.xml");
-            let server_xml_raw = fs::read_to_string(&server_xml_path)?;
-            let mut server_doc = Document::parse_str(&server_xml_raw)
-                .map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
-
-            if let Some(root) = server_doc.root_element() {
-                root.set_attribute(&mut server_doc, "port", p_stop.to_string());
-            }
-
-            let connectors = collect_connectors(&server_doc);
-            for connector in connectors {
-                let protocol: String = connector
-                    .attribute(&server_doc, "protocol")
-                    .unwrap_or("http/1.1")
-                    .to_lowercase();
-                if protocol.contains("http") || protocol == "http/1.1" {
-                    connector.set_attribute(&mut server_doc, "port", p_http.to_string());
-                    connector.set_attribute(&mut server_doc, "redirectPort", p_ssl.to_string());
-                } else if protocol.contains("ajp") {
-                    connector.set_attribute(&mut server_doc, "port", p_ajp.to_string());
-                    connector.set_attribute(&mut server_doc, "redirectPort", p_ssl.to_string());
-                }
-            }
-
-            let mut server_output = Vec::new();
-            server_doc
-                .write(&mut server_output)
-                .map_err(|e| anyhow::anyhow!("{}", e))?;
-            fs::write(&server_xml_path, server_output)?;
-
-            let context_xml_path = tomcat.join("conf/context.xml");
-            let context_xml_raw = fs::read_to_string(&context_xml_path)?;
-            let mut context_doc = Document::parse_str(&context_xml_raw)
-                .map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
-            if let Some(root) = context_doc.root_element() {
-                root.set_attribute(&mut context_doc, "sessionCookieName", cookie.clone());
-            }
-            let mut context_output = Vec::new();
-            context_doc
-                .write(&mut context_output)
-                .map_err(|e| anyhow::anyhow!("{}", e))?;
-            fs::write(context_xml_path, context_output)?;
-
-            let prop_path = bundles.join("portal-ext.properties");
-            let props_content = fs::read_to_string(&prop_path).unwrap_or_default();
-            let mut new_props: Vec<String> = props_content
-                .lines()
-                .filter(|l| {
-                    !l.starts_with("session.cookie.name") && !l.starts_with("jdbc.default.url")
-                })
-                .map(|s| s.to_string())
-                .collect();
-            new_props.push(format!("session.cookie.name={}", cookie));
-            new_props.push(format!("jdbc.default.url=jdbc:hsqldb:${{liferay.home}}/data/hypersonic/{};hsqldb.write_delay=false", db));
-            fs::write(prop_path, new_props.join("\n"))?;
+            if let Some(host) = &host {
+                let prop_path = bundles.join("portal-ext.properties");
+                let mut props = PropertiesDocument::parse(&changes.read_or_default(&prop_path)?);
+                apply_host(&mut props, host);
+                changes.write(&prop_path, props.to_string());
+            }
+
+            if no_wizard {
+                let settings = WizardSettings {
+                    company_name,
+                    admin_screen_name,
+                    admin_email,
+                    admin_password,
+                    locale,
+                    timezone,
+                };
+                let prop_path = bundles.join("portal-ext.properties");
+                let mut props = PropertiesDocument::parse(&changes.read_or_default(&prop_path)?);
+                settings.apply(&mut props)?;
+                changes.write(&prop_path, props.to_string());
+            }
+
+            let setenv_path = tomcat.join("bin/setenv.sh");
+            let mut setenv = SetEnv::parse(&changes.read_or_default(&setenv_path)?);
+            setenv.set_variable("JPDA_ADDRESS", &format!("localhost:{}", ports.debug));
+            if let Some(heap) = &heap {
+                setenv.set_heap(heap);
+            }
+            setenv.set_options(&jvm_opts);
+            if let Some(java) = &java {
+                let jdk = select_jdk(java)?;
+                println!("Pinning Java {} ({})", jdk.describe(), jdk.home.display());
+                if let Some(warning) =
+                    BundleVersion::detect(&bundles, &tomcat).java_warning(jdk.major())
+                {
+                    println!("WARNING: {}", warning);
+                }
+                setenv.set_variable("JAVA_HOME", &jdk.home.to_string_lossy());
+            }
+            changes.write(&setenv_path, setenv.to_string());
+
+            if https {
+                let pinned = setenv.variable("JAVA_HOME").map(PathBuf::from);
+                enable_https(
+                    &mut changes,
+                    &tomcat,
+                    &bundles,
+                    &ports,
+                    pinned,
+                    args.dry_run,
+                )?;
+            }
 
             if clear_data {
-                let _ = fs::remove_dir_all(bundles.join("data"));
+                changes.remove_dir(&bundles.join("data"));
             }
 
+            reconcile_properties(&mut changes, &bundles, None, !args.dry_run)?;
+
+            let operation = format!("configure {}", instance_id);
+            if !commit_changes(&changes, &bundles, &operation, args.dry_run, args.diff)? {
+                return Ok(());
+            }
             println!("Success! Instance {} configured.", instance_id);
             Ok(())
         }
 
+        AppCommands::Jvm {
+            instance_id,
+            action,
+            workspace_path,
+        } => {
+            let root = match workspace_path {
+                Some(path) => path,
+                None => ws.find_instance(instance_id)?,
+            };
+            let tomcat = ws.find_tomcat(&root)?;
+            let bundles = ws.get_bundles_dir(&root);
+            let setenv_path = tomcat.join("bin/setenv.sh");
+
+            let mut changes = ChangeSet::new();
+            let mut setenv = SetEnv::parse(&changes.read_or_default(&setenv_path)?);
+            let operation = match action {
+                JvmCommands::Get => {
+                    println!("{:<25} {:<45}", "Managed Options", setenv.options.join(" "));
+                    println!(
+                        "{:<25} {:<45}",
+                        "Effective Options",
+                        setenv.effective_options().join(" ")
+                    );
+                    return Ok(());
+                }
+                JvmCommands::Set { options, heap } => {
+                    if options.is_empty() && heap.is_none() {
+                        anyhow::bail!("Nothing to set. Pass --opt and/or --heap.");
+                    }
+                    if let Some(heap) = &heap {
+                        setenv.set_heap(heap);
+                    }
+                    setenv.set_options(&options);
+                    format!("jvm {} set", instance_id)
+                }
+                JvmCommands::Reset => {
+                    setenv.clear_options();
+                    format!("jvm {} reset", instance_id)
+                }
+            };
+            changes.write(&setenv_path, setenv.to_string());
+
+            if !commit_changes(&changes, &bundles, &operation, args.dry_run, args.diff)? {
+                return Ok(());
+            }
+            println!(
+                "JVM options of instance {}: {}",
+                instance_id,
+                setenv.effective_options().join(" ")
+            );
+            Ok(())
+        }
+
         AppCommands::Summary => {
             let root = ws.find_root()?;
             let project_type = ws.detect_type(&root);
             let tomcat = ws.find_tomcat(&root)?;
             let bundles = ws.get_bundles_dir(&root);
+            let setenv = SetEnv::parse(
+                &fs::read_to_string(tomcat.join("bin/setenv.sh")).unwrap_or_default(),
+            );
 
             println!("\n{:<25} {:<45}", "PROPERTY", "VALUE");
             println!("{}", "=".repeat(70));
@@ -222,24 +837,91 @@ fn main() -> anyhow::Result<()> {
             };
             println!("{:<25} {:<45}", "Project Type", project_desc);
             println!("{:<25} {:<45}", "Liferay Home", bundles.to_string_lossy());
-            if let Ok(output) = Command::new("java").arg("-version").output() {
-                let v = String::from_utf8_lossy(&output.stderr)
-                    .lines()
-                    .next()
-                    .unwrap_or("-")
-                    .to_string();
+            let pinned = setenv.variable("JAVA_HOME").map(PathBuf::from);
+            let mut java_major = None;
+            match jdk::active(pinned.as_deref()) {
+                Some((jdk, source)) => {
+                    println!(
+                        "{:<25} {:<45}",
+                        "Java Version",
+                        format!("{}, {}", jdk.describe(), source)
+                    );
+                    println!("{:<25} {:<45}", "Java Home", jdk.home.to_string_lossy());
+                    java_major = Some(jdk.major());
+                }
+                None => match &pinned {
+                    Some(home) => println!(
+                        "{:<25} {:<45}",
+                        "WARNING",
+                        format!("Pinned JDK {} not found", home.display())
+                    ),
+                    None => println!("{:<25} {:<45}", "Java Version", "No JDK found"),
+                },
+            }
+            let installed: Vec<String> = jdk::discover()
+                .iter()
+                .map(|jdk| jdk.major().to_string())
+                .collect();
+            if !installed.is_empty() {
+                println!("{:<25} {:<45}", "Installed JDKs", installed.join(", "));
+            }
+
+            println!("{:<25} {:<45}", "Tomcat", tomcat.to_string_lossy());
+            for stale in ws
+                .find_tomcat_candidates(&root)?
+                .iter()
+                .filter(|c| **c != tomcat)
+            {
                 println!(
                     "{:<25} {:<45}",
-                    "Java Version",
-                    v.replace("java version ", "").replace("\"", "")
+                    "WARNING: Stale Tomcat",
+                    stale.to_string_lossy()
                 );
             }
 
-            if let Some(version) = ws.get_liferay_version(&root) {
-                println!("{:<25} {:<45}", "Liferay Product", version);
+            let bundle_version = BundleVersion::detect(&bundles, &tomcat);
+            match (&bundle_version.product, &bundle_version.version) {
+                (Some(product), Some(version)) => println!(
+                    "{:<25} {:<45}",
+                    "Liferay Product",
+                    format!("{} {}", product, version)
+                ),
+                (None, Some(version)) => println!("{:<25} {:<45}", "Liferay Product", version),
+                _ => {
+                    if let Some(version) = ws.get_liferay_version(&root) {
+                        println!("{:<25} {:<45}", "Liferay Product", version);
+                    }
+                }
+            }
+            if let Some(build) = &bundle_version.build_number {
+                println!("{:<25} {:<45}", "Build Number", build);
+            }
+            if bundle_version.version.is_some() {
+                println!(
+                    "{:<25} {:<45}",
+                    "Release Type",
+                    bundle_version.release_type()
+                );
+            }
+            if let Some(tomcat_version) = &bundle_version.tomcat_version {
+                println!("{:<25} {:<45}", "Tomcat Version", tomcat_version);
+            }
+            let jvm_options = setenv.effective_options();
+            if !jvm_options.is_empty() {
+                println!("{:<25} {:<45}", "JVM Options", jvm_options.join(" "));
+            }
+            if let Some(warning) = java_major.and_then(|m| bundle_version.java_warning(m)) {
+                println!("{:<25} {:<45}", "WARNING", warning);
+            }
+            if !bundle_version.liferay_home {
+                println!(
+                    "{:<25} {:<45}",
+                    "WARNING", "No .liferay-home marker in Liferay Home"
+                );
             }
 
             let mut current_http: u16 = 8080;
+            let mut current_https: Option<u16> = None;
             if let Ok(content) = fs::read_to_string(tomcat.join("conf/server.xml")) {
                 if let Ok(doc) = Document::parse_str(&content) {
                     if let Some(r) = doc.root_element() {
@@ -249,115 +931,335 @@ fn main() -> anyhow::Result<()> {
                             r.attribute(&doc, "port").unwrap_or("8005")
                         );
 
-                        for node in collect_connectors(&doc) {
+                        for (role, node) in classify_connectors(&doc) {
                             let port_str: &str = node.attribute(&doc, "port").unwrap_or("-");
-                            let proto: String = node
-                                .attribute(&doc, "protocol")
-                                .unwrap_or("http")
-                                .to_lowercase();
-
-                            if proto.contains("ajp") {
-                                println!("{:<25} {:<45}", "AJP Port", port_str);
-                            } else {
-                                println!("{:<25} {:<45}", "HTTP Port", port_str);
-                                if let Ok(p) = port_str.parse::<u16>() {
-                                    current_http = p;
-                                }
-                                println!(
-                                    "{:<25} {:<45}",
-                                    "HTTPS Redirect",
-                                    node.attribute(&doc, "redirectPort").unwrap_or("8443")
-                                );
+                            match (role, port_str.parse::<u16>()) {
+                                (ConnectorRole::Http, Ok(p)) => current_http = p,
+                                (ConnectorRole::Https, Ok(p)) => current_https = Some(p),
+                                _ => {}
+                            }
+                            let mut value = format!(
+                                "{} ({})",
+                                port_str,
+                                node.attribute(&doc, "protocol").unwrap_or("HTTP/1.1")
+                            );
+                            if let Some(redirect) = node.attribute(&doc, "redirectPort") {
+                                value.push_str(&format!(", redirect {}", redirect));
                             }
+                            println!(
+                                "{:<25} {:<45}",
+                                format!("{} Connector", role.label()),
+                                value
+                            );
                         }
                     }
                 }
             }
 
-            let mut es_port = "9200 (Default)".to_string();
-            if let Ok(c) =
-                fs::read_to_string(bundles.join("elasticsearch/config/elasticsearch.yml"))
-            {
-                for line in c.lines() {
-                    if line.contains("http.port:") {
-                        es_port = line.split(':').nth(1).unwrap_or("9200").trim().to_string();
+            let es_port = fs::read_to_string(bundles.join(ELASTICSEARCH_CONFIG))
+                .ok()
+                .and_then(|c| elasticsearch_port(&PropertiesDocument::parse(&c)))
+                .map(|port| port.to_string())
+                .unwrap_or_else(|| "auto (Default)".to_string());
+            println!("{:<25} {:<45}", "Elasticsearch Port", es_port);
+
+            let props = PropertiesDocument::load(&bundles.join("portal-ext.properties"))
+                .unwrap_or_default();
+            let gogo = gogo_port(&props)
+                .map(|port| port.to_string())
+                .unwrap_or_else(|| "11311 (Default)".to_string());
+            println!("{:<25} {:<45}", "Gogo Shell Port", gogo);
+            if let Some(port) = setenv.jmx_port() {
+                println!("{:<25} {:<45}", "JMX Port", port);
+            }
+            if let Some(port) = setenv.debug_port() {
+                println!("{:<25} {:<45}", "Debug Port", debug_state(port));
+            }
+
+            let session_cookie = props
+                .get("session.cookie.name")
+                .unwrap_or("JSESSIONID (Default)");
+            println!("{:<25} {:<45}", "Session Cookie", session_cookie);
+            println!(
+                "{:<25} {:<45}",
+                "URL",
+                instance_url(&props, false, current_http)
+            );
+            if let Some(port) = current_https {
+                println!(
+                    "{:<25} {:<45}",
+                    "HTTPS URL",
+                    instance_url(&props, true, port)
+                );
+            }
+            match DatabaseConfig::from_properties(&props) {
+                Some(database) => {
+                    println!("{:<25} {:<45}", "Database", database.kind.label());
+                    println!("{:<25} {:<45}", "Database URL", database.url());
+                    println!("{:<25} {:<45}", "Database User", database.user);
+                    println!(
+                        "{:<25} {:<45}",
+                        "Database Password",
+                        database.masked_password()
+                    );
+                    if database.find_driver_jar(&tomcat).is_none() {
+                        println!(
+                            "{:<25} {:<45}",
+                            "WARNING",
+                            format!("{} JDBC driver jar missing", database.kind.label())
+                        );
                     }
                 }
+                None => {
+                    let db_url = props
+                        .get("jdbc.default.url")
+                        .unwrap_or("Default (HSQL in-memory)");
+                    println!("{:<25} {:<45}", "Database URL", db_url);
+                }
             }
-            println!("{:<25} {:<45}", "Elasticsearch Port", es_port);
 
-            let offset = (current_http / 100).saturating_sub(80);
-            println!("{:<25} {:<45}", "Gogo Shell Port", 11311 + (offset * 100));
+            println!("{}", "=".repeat(70));
+            Ok(())
+        }
+
+        AppCommands::Doctor {
+            instance_id,
+            json,
+            workspace_path,
+        } => {
+            let root = match (workspace_path, instance_id) {
+                (Some(path), _) => path,
+                (None, Some(id)) => ws.find_instance(id)?,
+                (None, None) => ws.find_root()?,
+            };
+            let instance_id = instance_id.or_else(|| ws.get_instance_id(&root));
+            let checks = doctor::run(&ws, &root, instance_id, &LocalConfig::load()?.ports);
+            let status = doctor::overall(&checks);
 
-            let mut session_cookie = "JSESSIONID (Default)".to_string();
-            let mut db_url = "Default (HSQL in-memory)".to_string();
-            if let Ok(content) = fs::read_to_string(bundles.join("portal-ext.properties")) {
-                for line in content.lines() {
-                    if line.starts_with("session.cookie.name") {
-                        session_cookie = line.split('=').nth(1).unwrap_or("-").trim().to_string();
-                    }
-                    if line.starts_with("jdbc.default.url") {
-                        db_url = line.split('=').nth(1).unwrap_or("-").trim().to_string();
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "status": status,
+                        "checks": checks,
+                    }))?
+                );
+            } else {
+                println!("{:<6} {:<24} DETAILS", "STATUS", "CHECK");
+                println!("{}", "-".repeat(70));
+                for check in &checks {
+                    println!(
+                        "{:<6} {:<24} {}",
+                        check.status.label(),
+                        check.name,
+                        check.message
+                    );
+                    if let Some(hint) = &check.hint {
+                        println!("{:<6} {:<24} -> {}", "", "", hint);
                     }
                 }
             }
-            println!("{:<25} {:<45}", "Session Cookie", session_cookie);
-            println!("{:<25} {:<45}", "Database URL", db_url);
+            if status == CheckStatus::Fail {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
 
-            println!("{}", "=".repeat(70));
+        AppCommands::Conflicts {
+            fix,
+            workspace_path,
+        } => {
+            let root = workspace_path.unwrap_or(ws.find_root()?);
+            let bundles = ws.get_bundles_dir(&root);
+            let mut changes = ChangeSet::new();
+            if reconcile_properties(&mut changes, &bundles, fix, !args.dry_run)? == 0 {
+                println!("No conflicting portal properties found.");
+                return Ok(());
+            }
+            commit_changes(&changes, &bundles, "conflicts", args.dry_run, args.diff)?;
             Ok(())
         }
 
         AppCommands::Status { instance_id } => {
+            let plan = LocalConfig::load()?.ports;
             let mut sys = System::new_all();
             sys.refresh_all();
             println!(
-                "{:<12} {:<10} {:<10} {:<10}",
-                "INSTANCE ID", "PORT", "STATUS", "PID"
+                "{:<12} {:<10} {:<10} {:<10} {:<26} {:<30}",
+                "INSTANCE ID", "PORT", "STATUS", "PID", "DEBUG", "URL"
             );
-            println!("{}", "-".repeat(45));
+            println!("{}", "-".repeat(103));
             let ids = match instance_id {
                 Some(id) => id..=id,
                 None => 0..=5,
             };
             for id in ids {
-                let port = 8080 + (id * 100);
-                let addr = format!("127.0.0.1:{}", port);
-                let is_open = if let Ok(mut addrs) = addr.to_socket_addrs() {
-                    if let Some(s) = addrs.next() {
-                        TcpStream::connect_timeout(&s, Duration::from_millis(50)).is_ok()
-                    } else {
-                        false
-                    }
-                } else {
-                    false
+                let Ok(ports) = plan.ports_for(id) else {
+                    continue;
                 };
-                if is_open {
+                let port = ports.http;
+                let props = ws
+                    .find_instance(id)
+                    .ok()
+                    .and_then(|root| {
+                        PropertiesDocument::load(
+                            &ws.get_bundles_dir(&root).join("portal-ext.properties"),
+                        )
+                        .ok()
+                    })
+                    .unwrap_or_default();
+                let url = instance_url(&props, false, port);
+                if is_port_open(port) {
                     let pid = sys
                         .processes()
                         .values()
                         .find(|p| p.name().to_lowercase().contains("java"))
                         .map(|p| p.pid().to_string())
                         .unwrap_or_else(|| "Unknown".to_string());
-                    println!("{:<12} {:<10} {:<10} {:<10}", id, port, "RUNNING", pid);
+                    println!(
+                        "{:<12} {:<10} {:<10} {:<10} {:<26} {:<30}",
+                        id,
+                        port,
+                        "RUNNING",
+                        pid,
+                        debug_state(ports.debug),
+                        url
+                    );
                 } else {
-                    println!("{:<12} {:<10} {:<10} {:<10}", id, port, "STOPPED", "-");
+                    println!(
+                        "{:<12} {:<10} {:<10} {:<10} {:<26} {:<30}",
+                        id, port, "STOPPED", "-", "-", url
+                    );
                 }
             }
             Ok(())
         }
 
+        AppCommands::Start {
+            instance_id,
+            debug,
+            suspend,
+            workspace_path,
+        } => {
+            let root = match workspace_path {
+                Some(path) => path,
+                None => ws.find_instance(instance_id)?,
+            };
+            let tomcat = ws.find_tomcat(&root)?;
+            let ports = LocalConfig::load()?.ports.ports_for(instance_id)?;
+            if is_port_open(ports.http) {
+                anyhow::bail!("Instance {} is already running.", instance_id);
+            }
+
+            let setenv = SetEnv::parse(
+                &fs::read_to_string(tomcat.join("bin/setenv.sh")).unwrap_or_default(),
+            );
+            let debug_port = setenv.debug_port().unwrap_or(ports.debug);
+            let script = if cfg!(windows) {
+                "catalina.bat"
+            } else {
+                "catalina.sh"
+            };
+            let mut command = Command::new(tomcat.join("bin").join(script));
+            command.current_dir(tomcat.join("bin"));
+            if debug {
+                command
+                    .args(["jpda", "start"])
+                    .env("JPDA_ADDRESS", format!("localhost:{}", debug_port))
+                    .env("JPDA_SUSPEND", if suspend { "y" } else { "n" });
+            } else {
+                command.arg("start");
+            }
+
+            let status = command
+                .status()
+                .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", script, e))?;
+            if !status.success() {
+                anyhow::bail!("{} exited with {}", script, status);
+            }
+            println!(
+                "Instance {} starting on http://localhost:{}",
+                instance_id, ports.http
+            );
+            if debug {
+                println!(
+                    "Debugger port: {}{}",
+                    debug_port,
+                    if suspend {
+                        " (waiting for a debugger to attach)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            Ok(())
+        }
+
+        AppCommands::Ide {
+            instance_id,
+            intellij,
+            vscode,
+            workspace_path,
+        } => {
+            let (root, bundle_root) = match workspace_path {
+                Some(path) => (path.clone(), path),
+                None => (ws.find_root()?, ws.find_instance(instance_id)?),
+            };
+            let tomcat = ws.find_tomcat(&bundle_root)?;
+            let setenv = SetEnv::parse(
+                &fs::read_to_string(tomcat.join("bin/setenv.sh")).unwrap_or_default(),
+            );
+            let debug_port = match setenv.debug_port() {
+                Some(port) => port,
+                None => LocalConfig::load()?.ports.ports_for(instance_id)?.debug,
+            };
+            let bin = tomcat.join("bin");
+            let instance = IdeInstance {
+                instance_id,
+                debug_port,
+                tomcat_bin: bin
+                    .strip_prefix(&root)
+                    .unwrap_or(&bin)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+            };
+
+            let mut changes = ChangeSet::new();
+            if intellij {
+                let dir = root.join(".idea/runConfigurations");
+                for (name, contents) in instance.intellij() {
+                    changes.write(&dir.join(name), contents);
+                }
+            }
+            if vscode {
+                let launch = root.join(".vscode/launch.json");
+                let merged = instance.merge_vscode_launch(&changes.read_or_default(&launch)?)?;
+                changes.write(&launch, merged);
+                let tasks = root.join(".vscode/tasks.json");
+                let merged = instance.merge_vscode_tasks(&changes.read_or_default(&tasks)?)?;
+                changes.write(&tasks, merged);
+            }
+            let bundles = ws.get_bundles_dir(&bundle_root);
+            let operation = format!("ide {}", instance_id);
+            if !commit_changes(&changes, &bundles, &operation, args.dry_run, args.diff)? {
+                return Ok(());
+            }
+            println!(
+                "Wrote '{}' and '{}' (debug port {}) to {}",
+                instance.start_name(),
+                instance.attach_name(),
+                debug_port,
+                root.display()
+            );
+            Ok(())
+        }
+
         AppCommands::Kill { instance_id } => {
             let mut sys = System::new_all();
             sys.refresh_all();
-            let port = 8080 + (instance_id * 100);
-            let addr = format!("127.0.0.1:{}", port);
-            if let Ok(mut addrs) = addr.to_socket_addrs() {
-                if let Some(s) = addrs.next() {
-                    if TcpStream::connect_timeout(&s, Duration::from_millis(50)).is_err() {
-                        anyhow::bail!("Instance {} not running.", instance_id);
-                    }
-                }
+            let port = LocalConfig::load()?.ports.ports_for(instance_id)?.http;
+            if !is_port_open(port) {
+                anyhow::bail!("Instance {} not running.", instance_id);
             }
             let process = sys
                 .processes()
@@ -374,56 +1276,118 @@ fn main() -> anyhow::Result<()> {
             all,
             props,
             ports,
+            https,
         } => {
             let root = workspace_path.unwrap_or(ws.find_root()?);
             let tomcat = ws.find_tomcat(&root)?;
             let bundles = ws.get_bundles_dir(&root);
 
+            let mut changes = ChangeSet::new();
+
+            let baseline = Baseline::new(&bundles);
+            if (props || ports) && !baseline.exists() {
+                println!(
+                    "WARNING: No baseline recorded for this bundle; falling back to stock Tomcat values."
+                );
+            }
+
             if props {
-                println!("Resetting session cookies and database URLs in portal-ext.properties...");
+                println!(
+                    "Restoring session cookies, database URLs and host names from the baseline..."
+                );
+                let original =
+                    PropertiesDocument::parse(&baseline.portal_ext().unwrap_or_default());
                 let prop_path = bundles.join("portal-ext.properties");
-                if let Ok(content) = fs::read_to_string(&prop_path) {
-                    let filtered: Vec<String> = content
-                        .lines()
-                        .filter(|l| {
-                            !l.starts_with("session.cookie.name")
-                                && !l.starts_with("jdbc.default.url")
-                        })
-                        .map(|s| s.to_string())
-                        .collect();
-                    let _ = fs::write(prop_path, filtered.join("\n"));
+                if let Ok(content) = changes.read(&prop_path) {
+                    let mut props = PropertiesDocument::parse(&content);
+                    for key in std::iter::once(&"session.cookie.name")
+                        .chain(JDBC_KEYS)
+                        .chain(HOST_KEYS)
+                        .chain(WIZARD_KEYS)
+                    {
+                        match original.get(key) {
+                            Some(value) => props.set(key, value),
+                            None => {
+                                props.remove(key);
+                            }
+                        }
+                    }
+                    changes.write(&prop_path, props.to_string());
+                }
+
+                let context_xml_path = tomcat.join("conf/context.xml");
+                if let Ok(raw) = changes.read(&context_xml_path) {
+                    let context = baseline.context_xml();
+                    changes.write(
+                        &context_xml_path,
+                        restore_session_cookie(&raw, context.as_deref())?,
+                    );
                 }
             }
 
             if ports {
-                println!("Resetting server.xml ports to 8080/8005 defaults...");
+                println!("Restoring ports from the baseline...");
                 let server_xml_path = tomcat.join("conf/server.xml");
-                if let Ok(raw) = fs::read_to_string(&server_xml_path) {
-                    if let Ok(mut doc) = Document::parse_str(&raw) {
-                        if let Some(root) = doc.root_element() {
-                            root.set_attribute(&mut doc, "port", "8005");
-                            for connector in collect_connectors(&doc) {
-                                let protocol: String = connector
-                                    .attribute(&doc, "protocol")
-                                    .unwrap_or("http/1.1")
-                                    .to_lowercase();
-                                if protocol.contains("http") {
-                                    connector.set_attribute(&mut doc, "port", "8080");
-                                    connector.set_attribute(&mut doc, "redirectPort", "8443");
-                                } else if protocol.contains("ajp") {
-                                    connector.set_attribute(&mut doc, "port", "8009");
-                                    connector.set_attribute(&mut doc, "redirectPort", "8443");
-                                }
-                            }
-                            let mut out = Vec::new();
-                            let _ = doc.write(&mut out);
-                            let _ = fs::write(server_xml_path, out);
+                if let Ok(raw) = changes.read(&server_xml_path) {
+                    let restored = match baseline.server_xml() {
+                        Some(original) => restore_ports(&raw, &original)?,
+                        None => default_ports(&raw)?,
+                    };
+                    changes.write(&server_xml_path, restored);
+                }
+
+                let prop_path = bundles.join("portal-ext.properties");
+                if let Ok(content) = changes.read(&prop_path) {
+                    let original =
+                        PropertiesDocument::parse(&baseline.portal_ext().unwrap_or_default());
+                    let mut props = PropertiesDocument::parse(&content);
+                    match original.get(GOGO_KEY) {
+                        Some(value) => props.set(GOGO_KEY, value),
+                        None => {
+                            props.remove(GOGO_KEY);
                         }
                     }
+                    changes.write(&prop_path, props.to_string());
+                }
+
+                let es_config_path = bundles.join(ELASTICSEARCH_CONFIG);
+                if let Ok(content) = changes.read(&es_config_path) {
+                    let mut es_config = PropertiesDocument::parse(&content);
+                    clear_elasticsearch_port(&mut es_config);
+                    changes.write(&es_config_path, es_config.to_string());
+                }
+
+                let setenv_path = tomcat.join("bin/setenv.sh");
+                if let Ok(content) = changes.read(&setenv_path) {
+                    let mut setenv = SetEnv::parse(&content);
+                    setenv.clear_jmx();
+                    changes.write(&setenv_path, setenv.to_string());
+                }
+            }
+
+            if https {
+                let store = HttpsStore::new(&bundles, &tomcat);
+                match store.load() {
+                    Some(record) => {
+                        println!("Removing the HTTPS connector and keystore...");
+                        let server_xml_path = tomcat.join("conf/server.xml");
+                        let mut doc = Document::parse_str(&changes.read(&server_xml_path)?)
+                            .map_err(|e| anyhow::anyhow!("XML Error: {}", e))?;
+                        let original = baseline
+                            .server_xml()
+                            .and_then(|xml| Document::parse_str(&xml).ok());
+                        disable_connector(&mut doc, &record, original.as_ref())?;
+                        changes.write(&server_xml_path, write_xml(&doc)?);
+                        // Nothing is staged, so the keystore is removed (and backed up)
+                        changes.replace(&store.keystore(), &store.staged_keystore());
+                        changes.remove_dir(&store.dir());
+                    }
+                    None => println!("No HTTPS setup recorded by configure --https; skipping."),
                 }
             }
 
             if all
+                && !args.dry_run
                 && !Confirm::new()
                     .with_prompt("Wipe ALL data?")
                     .interact()
@@ -431,25 +1395,337 @@ fn main() -> anyhow::Result<()> {
             {
                 return Ok(());
             }
-            let _ = fs::remove_dir_all(bundles.join("osgi/state"));
-            let t_temp = tomcat.join("temp");
-            let t_work = tomcat.join("work");
-            let _ = fs::remove_dir_all(&t_temp);
-            let _ = fs::remove_dir_all(&t_work);
-            let _ = fs::create_dir_all(t_temp);
-            let _ = fs::create_dir_all(t_work);
+            changes.remove_dir(&bundles.join("osgi/state"));
+            changes.clear_dir(&tomcat.join("temp"));
+            changes.clear_dir(&tomcat.join("work"));
             if all {
-                let _ = fs::remove_dir_all(bundles.join("data"));
+                changes.remove_dir(&bundles.join("data"));
+            }
+
+            if !commit_changes(&changes, &bundles, "reset", args.dry_run, args.diff)? {
+                return Ok(());
+            }
+
+            if all {
+                let props = PropertiesDocument::load(&bundles.join("portal-ext.properties"))?;
+                if let Some(database) =
+                    DatabaseConfig::from_properties(&props).filter(|d| d.kind != DatabaseKind::Hsql)
+                {
+                    if Confirm::new()
+                        .with_prompt(format!(
+                            "Recreate {} database '{}'?",
+                            database.kind.label(),
+                            database.name
+                        ))
+                        .interact()
+                        .unwrap_or(false)
+                    {
+                        database.drop_database(&bundles)?;
+                        database.create_database()?;
+                    }
+                }
             }
             println!("Reset complete.");
             Ok(())
         }
+
+        AppCommands::Db {
+            action,
+            instance_id,
+            file,
+            workspace_path,
+        } => {
+            let root = match workspace_path {
+                Some(path) => path,
+                None => ws.find_instance(instance_id)?,
+            };
+            let bundles = ws.get_bundles_dir(&root);
+            let props = PropertiesDocument::load(&bundles.join("portal-ext.properties"))?;
+            let database = DatabaseConfig::from_properties(&props).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Instance {} has no jdbc.default.url configured.",
+                    instance_id
+                )
+            })?;
+            let target = format!("{} database '{}'", database.kind.label(), database.name);
+
+            let confirm = |verb: &str| {
+                Confirm::new()
+                    .with_prompt(format!("{} {}? All its data will be lost.", verb, target))
+                    .interact()
+                    .unwrap_or(false)
+            };
+
+            match action {
+                DbAction::Create => {
+                    println!("Creating {}...", target);
+                    database.create_database()?;
+                }
+                DbAction::Drop => {
+                    if !confirm("Drop") {
+                        return Ok(());
+                    }
+                    println!("Dropping {}...", target);
+                    database.drop_database(&bundles)?;
+                }
+                DbAction::Recreate => {
+                    if !confirm("Recreate") {
+                        return Ok(());
+                    }
+                    println!("Recreating {}...", target);
+                    database.drop_database(&bundles)?;
+                    database.create_database()?;
+                }
+                DbAction::Dump => {
+                    let file = file.unwrap_or_else(|| {
+                        PathBuf::from(format!(
+                            "{}-{}.sql",
+                            database.name,
+                            Local::now().format("%Y%m%d-%H%M%S")
+                        ))
+                    });
+                    println!("Dumping {} to {}...", target, file.display());
+                    database.dump(&file)?;
+                }
+                DbAction::Restore => {
+                    let file =
+                        file.ok_or_else(|| anyhow::anyhow!("restore requires --file <dump.sql>"))?;
+                    if !confirm("Restore into") {
+                        return Ok(());
+                    }
+                    println!("Restoring {} from {}...", target, file.display());
+                    database.restore(&file)?;
+                }
+            }
+            println!("Done.");
+            Ok(())
+        }
+
+        AppCommands::Snapshot {
+            action,
+            workspace_path,
+        } => {
+            let instance_id = match &action {
+                SnapshotCommands::Save { instance_id, .. }
+                | SnapshotCommands::Restore { instance_id, .. }
+                | SnapshotCommands::List { instance_id }
+                | SnapshotCommands::Delete { instance_id, .. } => *instance_id,
+            };
+            let root = match workspace_path {
+                Some(path) => path,
+                None => ws.find_instance(instance_id)?,
+            };
+            let bundles = ws.get_bundles_dir(&root);
+            let store = SnapshotStore::new(&bundles);
+            let http = LocalConfig::load()?.ports.ports_for(instance_id)?.http;
+            let ensure_stopped = || {
+                if is_port_open(http) {
+                    anyhow::bail!(
+                        "Instance {} is running. Stop it before taking or restoring snapshots.",
+                        instance_id
+                    );
+                }
+                Ok(())
+            };
+
+            match action {
+                SnapshotCommands::Save { name, .. } => {
+                    ensure_stopped()?;
+                    println!("Saving snapshot '{}' of instance {}...", name, instance_id);
+                    let archive = store.save(&name)?;
+                    println!("Snapshot written to {}", archive.display());
+                }
+                SnapshotCommands::Restore { name, .. } => {
+                    ensure_stopped()?;
+                    println!(
+                        "Restoring snapshot '{}' to instance {}...",
+                        name, instance_id
+                    );
+                    let mut changes = ChangeSet::new();
+                    store.restore(&name, &mut changes)?;
+                    let restored = if args.dry_run {
+                        changes.finish(true, args.diff)
+                    } else {
+                        save_pre_restore(&store, &changes, &bundles, instance_id, &name)
+                            .and_then(|_| changes.finish(false, args.diff))
+                    };
+                    store.clear_staging()?;
+                    if restored? {
+                        println!("Snapshot restored.");
+                    }
+                }
+                SnapshotCommands::List { .. } => {
+                    println!("{:<30} {:<12} {:<20}", "SNAPSHOT", "SIZE (MB)", "CREATED");
+                    println!("{}", "-".repeat(62));
+                    for snapshot in store.list() {
+                        println!(
+                            "{:<30} {:<12.1} {:<20}",
+                            snapshot.name,
+                            snapshot.size as f64 / 1_048_576.0,
+                            snapshot.created.format("%Y-%m-%d %H:%M:%S")
+                        );
+                    }
+                }
+                SnapshotCommands::Delete { name, .. } => {
+                    store.delete(&name)?;
+                    println!("Snapshot '{}' deleted.", name);
+                }
+            }
+            Ok(())
+        }
+
+        AppCommands::Export {
+            instance_id,
+            out,
+            include_data,
+            workspace_path,
+        } => {
+            let root = match workspace_path {
+                Some(path) => path,
+                None => ws.find_instance(instance_id)?,
+            };
+            let tomcat = ws.find_tomcat(&root)?;
+            let bundles = ws.get_bundles_dir(&root);
+            if is_port_open(LocalConfig::load()?.ports.ports_for(instance_id)?.http) {
+                println!(
+                    "WARNING: Instance {} is running; the export may be inconsistent.",
+                    instance_id
+                );
+            }
+
+            let out = ws.current_dir.join(out);
+            let manifest = ExportManifest::new(&bundles, &tomcat, instance_id, include_data)?;
+            println!("Exporting instance {} to {}...", instance_id, out.display());
+            export_bundle(&bundles, &out, &manifest)?;
+            println!("Success! Instance {} exported.", instance_id);
+            Ok(())
+        }
+
+        AppCommands::Import { archive, id, name } => {
+            let name = name.unwrap_or_else(|| {
+                TemplateCache::key_for_archive(
+                    &archive.file_name().unwrap_or_default().to_string_lossy(),
+                )
+            });
+            let target_dir = ws.current_dir.join(&name);
+            if target_dir.exists() {
+                anyhow::bail!("Directory '{}' already exists.", name);
+            }
+
+            println!(
+                "Importing {} into {}...",
+                archive.display(),
+                target_dir.display()
+            );
+            let manifest = import_bundle(&archive, &target_dir)?;
+            if let (Some(product), Some(version)) = (&manifest.product, &manifest.version) {
+                println!("Bundle: {} {}", product, version);
+            }
+
+            let tomcat = target_dir.join(&manifest.tomcat);
+            let props = PropertiesDocument::load(&target_dir.join("portal-ext.properties"))?;
+            let database = rebase_database(
+                &target_dir,
+                DatabaseConfig::from_properties(&props),
+                format!("lportal_{}", id),
+            )?;
+
+            println!(
+                "--- Reconfiguring: Instance {} (was {}) ---",
+                id, manifest.instance_id
+            );
+            let mut changes = ChangeSet::new();
+            let ports = plan_ports(&LocalConfig::load()?.ports, id, &tomcat, &[])?;
+            configure_instance(&mut changes, &tomcat, &target_dir, id, &ports, &database)?;
+
+            let setenv_path = tomcat.join("bin/setenv.sh");
+            let mut setenv = SetEnv::parse(&changes.read_or_default(&setenv_path)?);
+            if let Some(java_home) = rebase_setenv(&mut setenv, ports.debug) {
+                println!(
+                    "WARNING: The pinned JDK {} does not exist here; removed it from setenv.sh. Use `configure {} --java` to pin one.",
+                    java_home, id
+                );
+            }
+            changes.write(&setenv_path, setenv.to_string());
+
+            commit_changes(
+                &changes,
+                &target_dir,
+                &format!("import {}", id),
+                false,
+                args.diff,
+            )?;
+
+            println!("Success! Instance {} imported into '{}'.", id, name);
+            Ok(())
+        }
+
+        AppCommands::Undo { workspace_path } => {
+            let root = workspace_path.unwrap_or(ws.find_root()?);
+            let store = BackupStore::new(&ws.get_bundles_dir(&root));
+            if args.dry_run {
+                match store.history().pop() {
+                    Some(operation) => {
+                        println!(
+                            "Dry run: would revert '{}' ({}):",
+                            operation.operation, operation.timestamp
+                        );
+                        if let Some(snapshot) = &operation.snapshot {
+                            println!("  instance state from snapshot '{}'", snapshot);
+                        }
+                        for file in operation.files {
+                            println!("  {}", file.path.display());
+                        }
+                    }
+                    None => println!("Nothing to undo."),
+                }
+                return Ok(());
+            }
+            match store.undo()? {
+                Some(operation) => {
+                    println!(
+                        "Reverted '{}' ({}):",
+                        operation.operation, operation.timestamp
+                    );
+                    if let Some(snapshot) = &operation.snapshot {
+                        println!("  instance state from snapshot '{}'", snapshot);
+                    }
+                    for file in operation.files {
+                        println!("  {}", file.path.display());
+                    }
+                }
+                None => println!("Nothing to undo."),
+            }
+            Ok(())
+        }
+
+        AppCommands::History { workspace_path } => {
+            let root = workspace_path.unwrap_or(ws.find_root()?);
+            let store = BackupStore::new(&ws.get_bundles_dir(&root));
+            println!("{:<22} {:<20} {:<45}", "ID", "OPERATION", "FILES");
+            println!("{}", "-".repeat(87));
+            for operation in store.history().iter().rev() {
+                let files: Vec<String> = operation
+                    .files
+                    .iter()
+                    .map(|f| f.path.to_string_lossy().to_string())
+                    .collect();
+                println!(
+                    "{:<22} {:<20} {:<45}",
+                    operation.id,
+                    operation.operation,
+                    files.join(", ")
+                );
+            }
+            Ok(())
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::tomcat::collect_connectors;
     use edit_xml::Document;
 
     #[test]
@@ -463,4 +1739,77 @@ mod tests {
         doc.write(&mut output).unwrap();
         assert!(String::from_utf8(output).unwrap().contains("port=\"8180\""));
     }
+
+    #[test]
+    fn test_jvm_set_keeps_heap_apart_from_options() {
+        let app = App::try_parse_from([
+            "lfr-local",
+            "jvm",
+            "2",
+            "set",
+            "--opt",
+            "-Xss1m",
+            "--heap",
+            "4g",
+        ])
+        .unwrap();
+        let AppCommands::Jvm {
+            action: JvmCommands::Set { options, heap },
+            ..
+        } = app.command
+        else {
+            panic!("expected jvm set");
+        };
+        assert_eq!(options, vec!["-Xss1m"]);
+        assert_eq!(heap.as_deref(), Some("4g"));
+
+        assert!(
+            App::try_parse_from(["lfr-local", "jvm", "2", "set", "-Xss1m", "--heap", "4g"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_dry_run_is_refused_by_commands_that_cannot_preview() {
+        let drop = App::try_parse_from(["lfr-local", "--dry-run", "db", "drop", "3"]).unwrap();
+        assert!(check_dry_run(&drop).is_err());
+        let delete =
+            App::try_parse_from(["lfr-local", "snapshot", "delete", "3", "fresh", "--dry-run"])
+                .unwrap();
+        assert!(check_dry_run(&delete).is_err());
+
+        let restore = App::try_parse_from([
+            "lfr-local",
+            "--dry-run",
+            "snapshot",
+            "restore",
+            "3",
+            "fresh",
+        ])
+        .unwrap();
+        assert!(check_dry_run(&restore).is_ok());
+        let drop = App::try_parse_from(["lfr-local", "db", "drop", "3"]).unwrap();
+        assert!(check_dry_run(&drop).is_ok());
+    }
+
+    #[test]
+    fn test_overlay_configs_applies_environment_after_common() {
+        let dir = tempfile::tempdir().unwrap();
+        let configs = dir.path().join("configs");
+        fs::create_dir_all(configs.join("common/osgi/configs")).unwrap();
+        fs::create_dir_all(configs.join("local")).unwrap();
+        fs::write(configs.join("common/portal-ext.properties"), "env=common\n").unwrap();
+        fs::write(configs.join("common/osgi/configs/a.config"), "x=1\n").unwrap();
+        fs::write(configs.join("local/portal-ext.properties"), "env=local\n").unwrap();
+
+        let home = dir.path().join("bundles");
+        fs::create_dir_all(&home).unwrap();
+        overlay_configs(&configs, &home, "local").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(home.join("portal-ext.properties")).unwrap(),
+            "env=local\n"
+        );
+        assert!(home.join("osgi/configs/a.config").exists());
+    }
 }
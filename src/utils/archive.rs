@@ -4,20 +4,49 @@ use flate2::Compression;
 use sevenz_rust::{Password, SevenZReader};
 use std::fs::File;
 use std::path::{Path, PathBuf};
-use tar::{Archive, Builder};
-
-/// Creates a .tar.gz archive from a source directory
-#[allow(dead_code)]
-pub fn create_tar_gz(src_dir: &Path, dest_file: &Path) -> Result<(), String> {
+use tar::{Archive, Builder, Header};
+use walkdir::WalkDir;
+
+/// Creates a .tar.gz archive from a source directory. `filter` receives each path
+/// relative to `src_dir`; rejected directories are skipped along with their contents.
+/// `extra` files are written from memory at the root of the archive.
+pub fn create_tar_gz(
+    src_dir: &Path,
+    dest_file: &Path,
+    filter: impl Fn(&Path) -> bool,
+    extra: &[(&str, &[u8])],
+) -> Result<(), String> {
     let tar_gz =
         File::create(dest_file).map_err(|e| format!("Failed to create archive file: {}", e))?;
     let enc = GzEncoder::new(tar_gz, Compression::default());
     let mut tar = Builder::new(enc);
 
-    tar.append_dir_all(".", src_dir)
-        .map_err(|e| format!("Failed to bundle directory: {}", e))?;
+    let entries = WalkDir::new(src_dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.path().strip_prefix(src_dir).map(&filter).unwrap_or(false));
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory: {}", e))?;
+        let relative = entry.path().strip_prefix(src_dir).unwrap();
+        if entry.path() == dest_file {
+            continue;
+        }
+        tar.append_path_with_name(entry.path(), relative)
+            .map_err(|e| format!("Failed to bundle directory: {}", e))?;
+    }
+    for (name, contents) in extra {
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, name, *contents)
+            .map_err(|e| format!("Failed to add {}: {}", name, e))?;
+    }
 
-    tar.finish()
+    tar.into_inner()
+        .and_then(|enc| enc.finish())
+        .map(|_| ())
         .map_err(|e| format!("Failed to finish tar: {}", e))
 }
 
@@ -128,7 +157,6 @@ pub fn extract_tar_gz_stripped(
 }
 
 /// Extracts a .tar.gz archive to a destination directory
-#[allow(dead_code)]
 pub fn extract_tar_gz(archive_path: &Path, dest_dir: &Path) -> Result<(), String> {
     extract_tar_gz_stripped(archive_path, dest_dir, false)
 }
@@ -0,0 +1,239 @@
+use anyhow::Result;
+use similar::TextDiff;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file whose new contents have been computed but not yet written
+struct FileChange {
+    path: PathBuf,
+    original: Option<String>,
+    updated: String,
+}
+
+/// Collects every file mutation of a command in memory so it can be previewed
+/// as a diff (`--dry-run`) or applied in one go.
+#[derive(Default)]
+pub struct ChangeSet {
+    files: Vec<FileChange>,
+    /// Directories to empty; `true` if they should be recreated afterwards
+    cleared_dirs: Vec<(PathBuf, bool)>,
+    /// Files or directories to swap in: (target, prepared replacement)
+    replaced: Vec<(PathBuf, PathBuf)>,
+}
+
+impl ChangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a file as this change set would leave it
+    pub fn read(&self, path: &Path) -> io::Result<String> {
+        match self.files.iter().find(|f| f.path == path) {
+            Some(change) => Ok(change.updated.clone()),
+            None => fs::read_to_string(path),
+        }
+    }
+
+    /// Like `read`, but a missing file reads as empty
+    pub fn read_or_default(&self, path: &Path) -> io::Result<String> {
+        match self.read(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            other => other,
+        }
+    }
+
+    /// Stages new contents for a file
+    pub fn write(&mut self, path: &Path, contents: impl Into<String>) {
+        let updated = contents.into();
+        match self.files.iter_mut().find(|f| f.path == path) {
+            Some(change) => change.updated = updated,
+            None => self.files.push(FileChange {
+                path: path.to_path_buf(),
+                original: fs::read_to_string(path).ok(),
+                updated,
+            }),
+        }
+    }
+
+    /// Stages the removal of a directory and everything in it
+    pub fn remove_dir(&mut self, path: &Path) {
+        self.cleared_dirs.push((path.to_path_buf(), false));
+    }
+
+    /// Stages emptying a directory, leaving it in place
+    pub fn clear_dir(&mut self, path: &Path) {
+        self.cleared_dirs.push((path.to_path_buf(), true));
+    }
+
+    /// Stages moving `source` (a file or directory prepared on the same file system)
+    /// into the place of `target`. A missing `source` removes `target`.
+    pub fn replace(&mut self, target: &Path, source: &Path) {
+        self.replaced
+            .push((target.to_path_buf(), source.to_path_buf()));
+    }
+
+    /// Every staged `replace` target, to be backed up before they are swapped
+    pub fn replaced(&self) -> Vec<&Path> {
+        self.replaced
+            .iter()
+            .map(|(target, _)| target.as_path())
+            .collect()
+    }
+
+    /// Original contents (`None` for new files) of every file whose contents change
+    pub fn originals(&self) -> Vec<(&Path, Option<&str>)> {
+        self.files
+            .iter()
+            .filter(|f| f.original.as_deref() != Some(f.updated.as_str()))
+            .map(|f| (f.path.as_path(), f.original.as_deref()))
+            .collect()
+    }
+
+    /// Unified diff of every changed file, plus a line per directory removal or replacement
+    pub fn diff(&self) -> String {
+        let mut out = String::new();
+        for change in &self.files {
+            let original = change.original.as_deref().unwrap_or_default();
+            if original == change.updated {
+                continue;
+            }
+            let path = change.path.to_string_lossy();
+            let old_header = if change.original.is_some() {
+                format!("a/{}", path.trim_start_matches('/'))
+            } else {
+                "/dev/null".to_string()
+            };
+            out.push_str(
+                &TextDiff::from_lines(original, change.updated.as_str())
+                    .unified_diff()
+                    .context_radius(3)
+                    .header(&old_header, &format!("b/{}", path.trim_start_matches('/')))
+                    .to_string(),
+            );
+        }
+        for (dir, recreate) in &self.cleared_dirs {
+            if dir.exists() {
+                let verb = if *recreate { "Clear" } else { "Remove" };
+                out.push_str(&format!("{} directory {}\n", verb, dir.display()));
+            }
+        }
+        for (target, source) in &self.replaced {
+            if source.exists() {
+                out.push_str(&format!(
+                    "Replace {} with {}\n",
+                    target.display(),
+                    source.display()
+                ));
+            } else if target.exists() {
+                out.push_str(&format!("Remove {}\n", target.display()));
+            }
+        }
+        out
+    }
+
+    /// Writes every staged file and performs the directory removals and replacements
+    pub fn apply(&self) -> Result<()> {
+        for change in &self.files {
+            if change.original.as_deref() != Some(change.updated.as_str()) {
+                if let Some(parent) = change.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&change.path, &change.updated)?;
+            }
+        }
+        for (dir, recreate) in &self.cleared_dirs {
+            let _ = fs::remove_dir_all(dir);
+            if *recreate {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        for (target, source) in &self.replaced {
+            swap_in(target, source)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the changes, or only prints them when `dry_run` is set.
+    /// Returns whether anything was written.
+    pub fn finish(&self, dry_run: bool, show_diff: bool) -> Result<bool> {
+        if dry_run {
+            let diff = self.diff();
+            if diff.is_empty() {
+                println!("Dry run: no changes.");
+            } else {
+                println!("Dry run: the following changes would be made:\n");
+                print!("{}", diff);
+            }
+            return Ok(false);
+        }
+
+        let diff = if show_diff {
+            self.diff()
+        } else {
+            String::new()
+        };
+        self.apply()?;
+        if !diff.is_empty() {
+            print!("{}", diff);
+        }
+        Ok(true)
+    }
+}
+
+/// Moves `target` aside next to `source`, renames `source` into its place and only
+/// then deletes the old copy, putting it back if the rename fails
+fn swap_in(target: &Path, source: &Path) -> Result<()> {
+    let mut previous = source.as_os_str().to_owned();
+    previous.push(".previous");
+    let previous = PathBuf::from(previous);
+    let had_target = target.exists();
+    if had_target {
+        fs::rename(target, &previous)?;
+    }
+    if source.exists() {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(e) = fs::rename(source, target) {
+            if had_target {
+                fs::rename(&previous, target)?;
+            }
+            return Err(e.into());
+        }
+    }
+    if had_target {
+        if previous.is_dir() {
+            fs::remove_dir_all(&previous)?;
+        } else {
+            fs::remove_file(&previous)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_dry_run_leaves_files_untouched() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("portal-ext.properties");
+        fs::write(&file, "a=1\nb=2\n").unwrap();
+
+        let mut changes = ChangeSet::new();
+        changes.write(&file, "a=1\nb=3\n");
+        assert_eq!(changes.read(&file).unwrap(), "a=1\nb=3\n");
+
+        let diff = changes.diff();
+        assert!(diff.contains("-b=2\n+b=3\n"));
+
+        assert!(!changes.finish(true, false).unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a=1\nb=2\n");
+
+        assert!(changes.finish(false, false).unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a=1\nb=3\n");
+    }
+}
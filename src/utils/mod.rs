@@ -1,7 +1,10 @@
 pub mod archive;
+pub mod changes;
 pub mod download;
 pub mod git;
+pub mod net;
 pub mod process;
 
+pub use changes::ChangeSet;
 #[allow(unused_imports)]
 pub use process::CommandRunner;
@@ -0,0 +1,56 @@
+use std::fs;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Socket states as numbered in /proc/net/tcp
+const TCP_ESTABLISHED: u8 = 0x01;
+const TCP_LISTEN: u8 = 0x0A;
+
+/// Returns true if something is accepting connections on the local port
+pub fn is_port_open(port: u16) -> bool {
+    let addr = format!("127.0.0.1:{}", port);
+    match addr.to_socket_addrs() {
+        Ok(mut addrs) => addrs
+            .next()
+            .map(|s| TcpStream::connect_timeout(&s, Duration::from_millis(50)).is_ok())
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+/// States of the local TCP sockets bound to `port`, read from /proc/net/tcp{,6}.
+/// Always empty where procfs is not available.
+fn socket_states(port: u16) -> Vec<u8> {
+    let local_port = format!(":{:04X}", port);
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|table| {
+            table
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    let local = fields.nth(1)?;
+                    let state = fields.nth(1)?;
+                    if local.ends_with(&local_port) {
+                        u8::from_str_radix(state, 16).ok()
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<u8>>()
+        })
+        .collect()
+}
+
+/// Returns true if a client is connected to the local port. Unlike `is_port_open`
+/// this does not connect, so it is safe to use on a JDWP port.
+pub fn has_established_connection(port: u16) -> bool {
+    socket_states(port).contains(&TCP_ESTABLISHED)
+}
+
+/// Returns true if a socket is listening on the local port, without connecting to it
+pub fn is_listening(port: u16) -> bool {
+    socket_states(port).contains(&TCP_LISTEN)
+}